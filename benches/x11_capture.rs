@@ -0,0 +1,71 @@
+// runs against its own Xvfb, like capture_window_rect against a desktop window
+#[cfg(unix)]
+mod xvfb {
+    use std::process::{Child, Command};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use criterion::{black_box, criterion_group, Criterion};
+    use win_screenshot::prelude::*;
+
+    const DISPLAY: &str = ":97";
+
+    struct Xvfb(Child);
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start() -> Xvfb {
+        let x = Xvfb(
+            Command::new("Xvfb")
+                .args([DISPLAY, "-screen", "0", "1920x1080x24", "-nolisten", "tcp"])
+                .spawn()
+                .expect("Xvfb is not installed"),
+        );
+        for _ in 0..100 {
+            if X11Backend::connect_to(Some(DISPLAY)).is_ok() {
+                return x;
+            }
+            sleep(Duration::from_millis(50));
+        }
+        panic!("Xvfb {} did not come up", DISPLAY);
+    }
+
+    fn capturer(request: CaptureRequest, shm: bool) -> X11Capturer {
+        let mut backend = X11Backend::connect_to(Some(DISPLAY)).unwrap();
+        backend.set_shm(shm);
+        X11Capturer::with_backend(backend, request)
+    }
+
+    pub fn criterion_benchmark(c: &mut Criterion) {
+        let _x = start();
+        let display = CaptureRequest::display().format(PixelFormat::Bgra8);
+        let crop = display.region(Region::new(100, 100, 200, 200));
+
+        let mut group = c.benchmark_group("x11");
+        for (name, request) in [("display", display), ("crop", crop)] {
+            let mut slow = capturer(request, false);
+            group.bench_function(format!("{}_xgetimage", name), |b| {
+                b.iter(|| black_box(slow.capture().unwrap().width))
+            });
+            let mut fast = capturer(request, true);
+            assert!(fast.backend().uses_shm());
+            group.bench_function(format!("{}_shm", name), |b| {
+                b.iter(|| black_box(fast.capture().unwrap().width))
+            });
+        }
+        group.finish();
+    }
+
+    criterion_group!(benches, criterion_benchmark);
+}
+
+#[cfg(unix)]
+criterion::criterion_main!(xvfb::benches);
+
+#[cfg(not(unix))]
+fn main() {}
@@ -1,4 +1,6 @@
-use image::{DynamicImage, RgbaImage};
+#![allow(unused_variables)]
+
+use image::RgbaImage;
 use regex::Regex;
 use win_screenshot::prelude::*;
 
@@ -23,10 +25,11 @@ fn main() {
         .hwnd;
     let buf = capture_window(hwnd).unwrap();
 
-    // convert to image and save
-    let img = DynamicImage::ImageRgba8(
-        RgbaImage::from_raw(buf.width, buf.height, buf.pixels).unwrap());
-    img.to_rgb8().save("screenshot.jpg").unwrap();
+    // save, encoder is picked by extension (requires "image" feature)
+    buf.save("screenshot.jpg").unwrap();
+
+    // or convert to image::RgbaImage, RgbImage, DynamicImage
+    let img: RgbaImage = buf.try_into().unwrap();
 
     // Fine tuning
 
@@ -45,4 +48,25 @@ fn main() {
     let crop_xy = None; //Some([100, 100]);
     let crop_wh = None; //Some([300, 300]);
     let buf = capture_window_ex(hwnd, using, area, crop_xy, crop_wh).unwrap();
+
+    // Native GDI layout is Bgra8, Rgba8 by default
+    // unmirror fixes PrintWindow output for RTL (Arabic, Hebrew) windows
+    let output = Output {
+        format: PixelFormat::Bgra8,
+        unmirror: true,
+        ..Default::default()
+    };
+    let buf = capture_window_ex_with(hwnd, using, area, crop_xy, crop_wh, output).unwrap();
+
+    // Same knobs as a builder
+    let buf = CaptureRequest::window(hwnd)
+        .using(Using::BitBlt)
+        .area(Area::ClientOnly)
+        .region(Region::new(100, 100, 300, 300))
+        // Error (default), Clamp or PadWithColor when region leaves the window
+        .region_policy(RegionPolicy::Clamp)
+        .format(PixelFormat::Bgra8)
+        .dpi(Dpi::Keep)
+        .capture()
+        .unwrap();
 }
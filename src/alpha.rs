@@ -0,0 +1,35 @@
+use crate::buf::{PixelFormat, RgbBuf};
+
+// GetDIBits leaves the alpha byte of a compatible bitmap undefined, often 0
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AlphaMode {
+    Opaque,
+    Preserve,
+    PremultipliedToStraight,
+}
+
+impl RgbBuf {
+    pub fn apply_alpha(mut self, mode: AlphaMode) -> RgbBuf {
+        let a = match self.format {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => 3,
+            PixelFormat::Rgb8 | PixelFormat::Gray8 => return self,
+        };
+        apply_alpha(&mut self.pixels, a, mode);
+        self
+    }
+}
+
+// 4 byte pixels with alpha at index a, color before it
+pub(crate) fn apply_alpha(pixels: &mut [u8], a: usize, mode: AlphaMode) {
+    let px = pixels.chunks_exact_mut(4);
+    match mode {
+        AlphaMode::Opaque => px.for_each(|c| c[a] = 255),
+        AlphaMode::Preserve => {}
+        AlphaMode::PremultipliedToStraight => px.for_each(|c| match c[a] as u32 {
+            0 | 255 => {}
+            alpha => c[..a]
+                .iter_mut()
+                .for_each(|v| *v = ((*v as u32 * 255 + alpha / 2) / alpha).min(255) as u8),
+        }),
+    }
+}
@@ -0,0 +1,188 @@
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_core::Stream;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::backend::CaptureBackend;
+use crate::buf::RgbBuf;
+use crate::capture::{Area, Using};
+use crate::error::Error;
+use crate::request::CaptureRequest;
+use crate::stream::{CaptureStream, Clock, Frame};
+
+struct Job {
+    request: CaptureRequest,
+    reply: oneshot::Sender<Result<RgbBuf, Error>>,
+}
+
+// captures on a dedicated thread that owns the backend, so GDI resources stay
+// on one thread and PrintWindow never blocks a runtime worker; clones share the
+// thread, which exits once every clone is dropped
+#[derive(Clone)]
+pub struct AsyncCapturer {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl AsyncCapturer {
+    // platform backend, rebuilt only when a request asks for another Using;
+    // capacity requests wait for the thread before capture() itself waits, 0 counts as 1
+    pub fn new(capacity: usize) -> AsyncCapturer {
+        AsyncCapturer::spawn(capacity, || {
+            let mut current: Option<(Using, Box<dyn CaptureBackend>)> = None;
+            move |request: &CaptureRequest| {
+                if !matches!(&current, Some((using, _)) if *using == request.using) {
+                    current = Some((request.using, request.backend()?));
+                }
+                let (_, backend) = current.as_mut().expect("backend was just built");
+                request.capture_with(backend)
+            }
+        })
+    }
+
+    // using and dpi of requests are up to the backend
+    pub fn with_backend<B: CaptureBackend + Send + 'static>(
+        mut backend: B,
+        capacity: usize,
+    ) -> AsyncCapturer {
+        AsyncCapturer::spawn(capacity, move || {
+            move |request: &CaptureRequest| request.capture_with(&mut backend)
+        })
+    }
+
+    fn spawn<F, W>(capacity: usize, make: F) -> AsyncCapturer
+    where
+        F: FnOnce() -> W + Send + 'static,
+        W: FnMut(&CaptureRequest) -> Result<RgbBuf, Error>,
+    {
+        // tokio panics on an empty channel
+        let (jobs, mut rx) = mpsc::channel::<Job>(capacity.max(1));
+        thread::Builder::new()
+            .name("win-screenshot capture".into())
+            .spawn(move || {
+                let mut capture = make();
+                while let Some(job) = rx.blocking_recv() {
+                    // the caller stopped waiting, skip the grab
+                    if job.reply.is_closed() {
+                        continue;
+                    }
+                    let _ = job.reply.send(capture(&job.request));
+                }
+            })
+            .expect("spawn capture thread");
+        AsyncCapturer { jobs }
+    }
+
+    // dropping the future cancels the capture unless the thread already started it
+    pub async fn capture(&self, request: CaptureRequest) -> Result<RgbBuf, Error> {
+        let (reply, rx) = oneshot::channel();
+        self.jobs
+            .send(Job { request, reply })
+            .await
+            .map_err(|_| Error::CaptureThread)?;
+        rx.await.map_err(|_| Error::CaptureThread)?
+    }
+}
+
+// CaptureStream running on its own thread; at most capacity frames wait to be
+// read (0 counts as 1), when the reader falls behind the stream drops slots instead
+pub struct FrameStream {
+    frames: mpsc::Receiver<Result<Frame, Error>>,
+}
+
+impl FrameStream {
+    // platform backend, an fps that isn't positive or a failure to set up the
+    // backend is the only item
+    pub fn new(request: CaptureRequest, fps: f64, capacity: usize) -> FrameStream {
+        FrameStream::spawn(capacity, move || {
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err(Error::Fps(fps));
+            }
+            Ok(CaptureStream::with_backend(
+                request.backend()?,
+                request,
+                fps,
+            ))
+        })
+    }
+
+    pub fn from_stream<B, C>(stream: CaptureStream<B, C>, capacity: usize) -> FrameStream
+    where
+        B: CaptureBackend + Send + 'static,
+        C: Clock + Send + 'static,
+    {
+        FrameStream::spawn(capacity, move || Ok(stream))
+    }
+
+    // a setup error from make is the only item
+    pub(crate) fn spawn<B, C, F>(capacity: usize, make: F) -> FrameStream
+    where
+        B: CaptureBackend,
+        C: Clock,
+        F: FnOnce() -> Result<CaptureStream<B, C>, Error> + Send + 'static,
+    {
+        let (tx, frames) = mpsc::channel(capacity.max(1));
+        thread::Builder::new()
+            .name("win-screenshot stream".into())
+            .spawn(move || {
+                let stream = match make() {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(e));
+                        return;
+                    }
+                };
+                for frame in stream {
+                    // reader gone or cancelled
+                    if tx.blocking_send(frame).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("spawn stream thread");
+        FrameStream { frames }
+    }
+
+    // stops capturing, frames already captured can still be read
+    pub fn cancel(&mut self) {
+        self.frames.close();
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Result<Frame, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.frames.poll_recv(cx)
+    }
+}
+
+// one capture thread shared by the free functions below
+fn shared() -> &'static AsyncCapturer {
+    static SHARED: OnceLock<AsyncCapturer> = OnceLock::new();
+    SHARED.get_or_init(|| AsyncCapturer::new(16))
+}
+
+pub async fn capture_window_async(hwnd: isize) -> Result<RgbBuf, Error> {
+    capture_window_ex_async(hwnd, Using::PrintWindow, Area::Full, None, None).await
+}
+
+pub async fn capture_window_ex_async(
+    hwnd: isize,
+    using: Using,
+    area: Area,
+    crop_xy: Option<[i32; 2]>,
+    crop_wh: Option<[i32; 2]>,
+) -> Result<RgbBuf, Error> {
+    let request = CaptureRequest::window(hwnd)
+        .using(using)
+        .area(area)
+        .crop(crop_xy, crop_wh);
+    shared().capture(request).await
+}
+
+pub async fn capture_display_async() -> Result<RgbBuf, Error> {
+    shared().capture(CaptureRequest::display()).await
+}
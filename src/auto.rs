@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::capture::{Area, Using};
+use crate::request::Target;
+
+// Using::Auto state of one backend: the cheap method, the one to fall back to
+// when that fails or comes out blank, and which of them worked for each target
+#[derive(Debug, Clone)]
+pub struct AutoMethods {
+    first: Using,
+    fallback: Using,
+    full: Option<Using>,
+    worked: HashMap<Target, Using>,
+}
+
+impl AutoMethods {
+    pub fn new(first: Using, fallback: Using) -> AutoMethods {
+        AutoMethods {
+            first,
+            fallback,
+            full: None,
+            worked: HashMap::new(),
+        }
+    }
+
+    // the only method that captures Area::Full of a window, the pair would give
+    // frames of different sizes there
+    pub fn full_area(mut self, using: Using) -> AutoMethods {
+        self.full = Some(using);
+        self
+    }
+
+    // method to start target with and the one to fall back to, none once the
+    // fallback is what worked
+    pub(crate) fn methods(&self, target: Target, area: Area) -> (Using, Option<Using>) {
+        if let (Some(full), Target::Window(_), Area::Full) = (self.full, target, area) {
+            return (full, None);
+        }
+        match self.worked.get(&target) {
+            Some(&using) if using != self.first => (using, None),
+            _ => (self.first, Some(self.fallback)),
+        }
+    }
+
+    pub(crate) fn remember(&mut self, target: Target, using: Using) {
+        self.worked.insert(target, using);
+    }
+
+    // next capture of target starts over with the cheap method, e.g. after the
+    // window was recreated under the same handle
+    pub fn forget(&mut self, target: Target) {
+        self.worked.remove(&target);
+    }
+}
+
+// the GDI pair, BitBlt then PrintWindow
+impl Default for AutoMethods {
+    fn default() -> Self {
+        AutoMethods::new(Using::BitBlt, Using::PrintWindow)
+    }
+}
+
+// what one-off captures learned: capture_window_ex, CaptureRequest::capture and
+// the like set up a backend per call and it goes with them
+fn shared() -> MutexGuard<'static, AutoMethods> {
+    static SHARED: OnceLock<Mutex<AutoMethods>> = OnceLock::new();
+    SHARED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+// fresh backend picks up what earlier one-off captures of target found
+pub(crate) fn load_shared(auto: &mut AutoMethods, target: Target) {
+    match shared().worked.get(&target) {
+        Some(&using) => auto.remember(target, using),
+        None => auto.forget(target),
+    }
+}
+
+pub(crate) fn store_shared(auto: &AutoMethods, target: Target) {
+    if let Some(&using) = auto.worked.get(&target) {
+        shared().remember(target, using);
+    }
+}
+
+// method one-off Using::Auto captures settled on for target, None before the first
+pub fn auto_method(target: Target) -> Option<Using> {
+    shared().worked.get(&target).copied()
+}
+
+// next one-off Using::Auto capture of target starts over with the cheap method
+pub fn forget_auto(target: Target) {
+    shared().forget(target);
+}
+
+impl RgbBuf {
+    // what a failed BitBlt of a GPU window gives: every pixel black, or fully
+    // transparent, which premultiplied is black as well; alpha is ignored as
+    // GetDIBits leaves it undefined. Stops at the first colored pixel.
+    pub fn is_blank(&self) -> bool {
+        let c = self.format.bytes_per_pixel();
+        let color = match self.format {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => 3,
+            PixelFormat::Rgb8 | PixelFormat::Gray8 => c,
+        };
+        !self.pixels.is_empty()
+            && self
+                .pixels
+                .chunks_exact(c)
+                .all(|p| p[..color] == [0; 3][..color])
+    }
+}
@@ -0,0 +1,148 @@
+use crate::auto::AutoMethods;
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::capture::{Area, Using};
+use crate::error::Error;
+use crate::region::Region;
+use crate::request::Target;
+use crate::utils::HwndName;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Geometry {
+    pub width: i32,
+    pub height: i32,
+    // backend returns the target horizontally mirrored (RTL windows via PrintWindow)
+    pub mirrored: bool,
+}
+
+pub trait CaptureBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error>;
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error>;
+
+    // src is already fitted into geometry and never empty, pixels in any PixelFormat
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error>;
+
+    // like grab, backends that keep their own resources reuse the allocation of buf
+    fn grab_into(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        buf: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        *buf = self.grab(target, area, src)?;
+        Ok(())
+    }
+
+    // Using::Auto switches between methods through this, backends with a
+    // single method ignore it
+    fn set_using(&mut self, _using: Using) {}
+
+    // what Using::Auto tries and remembers on this backend, Auto fails with
+    // Error::Unsupported on backends without a second method
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        None
+    }
+
+    // Bgra8 rows of src into caller memory, stride bytes apart, sizes already checked
+    fn grab_to_slice(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(), Error> {
+        let buf = self.grab(target, area, src)?;
+        buf.check()?;
+        Ok(buf
+            .into_format(PixelFormat::Bgra8)
+            .copy_to_slice(dst, stride)?)
+    }
+}
+
+impl<B: CaptureBackend + ?Sized> CaptureBackend for &mut B {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        (**self).targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        (**self).geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        (**self).grab(target, area, src)
+    }
+
+    fn grab_into(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        buf: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        (**self).grab_into(target, area, src, buf)
+    }
+
+    fn set_using(&mut self, using: Using) {
+        (**self).set_using(using)
+    }
+
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        (**self).auto_methods()
+    }
+
+    fn grab_to_slice(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(), Error> {
+        (**self).grab_to_slice(target, area, src, dst, stride)
+    }
+}
+
+impl<B: CaptureBackend + ?Sized> CaptureBackend for Box<B> {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        (**self).targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        (**self).geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        (**self).grab(target, area, src)
+    }
+
+    fn grab_into(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        buf: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        (**self).grab_into(target, area, src, buf)
+    }
+
+    fn set_using(&mut self, using: Using) {
+        (**self).set_using(using)
+    }
+
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        (**self).auto_methods()
+    }
+
+    fn grab_to_slice(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(), Error> {
+        (**self).grab_to_slice(target, area, src, dst, stride)
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+
+use crate::region::Region;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PixelFormat {
+    Bgra8,
+    Rgba8,
+    Rgb8,
+    Gray8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+
+    pub(crate) fn read(self, px: &[u8]) -> [u8; 4] {
+        match self {
+            PixelFormat::Bgra8 => [px[2], px[1], px[0], px[3]],
+            PixelFormat::Rgba8 => [px[0], px[1], px[2], px[3]],
+            PixelFormat::Rgb8 => [px[0], px[1], px[2], 255],
+            PixelFormat::Gray8 => [px[0], px[0], px[0], 255],
+        }
+    }
+
+    pub(crate) fn write(self, [r, g, b, a]: [u8; 4], px: &mut [u8]) {
+        match self {
+            PixelFormat::Bgra8 => px.copy_from_slice(&[b, g, r, a]),
+            PixelFormat::Rgba8 => px.copy_from_slice(&[r, g, b, a]),
+            PixelFormat::Rgb8 => px.copy_from_slice(&[r, g, b]),
+            // BT.601 luma
+            PixelFormat::Gray8 => {
+                px[0] = ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BufError {
+    SizeMismatch { expected: usize, actual: usize },
+    OutOfBounds(Region),
+    // packed source pixels of a depth that can't be unpacked
+    BitsPerPixel(u32),
+    // caller memory can't hold the frame
+    TooSmall { expected: usize, actual: usize },
+    // caller stride is shorter than one row of pixels
+    Stride { stride: usize, row: usize },
+}
+
+impl fmt::Display for BufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufError::SizeMismatch { expected, actual } => {
+                write!(f, "pixel buffer is {} bytes, expected {}", actual, expected)
+            }
+            BufError::OutOfBounds(r) => write!(
+                f,
+                "region {}x{} at {},{} is out of bounds",
+                r.width, r.height, r.x, r.y
+            ),
+            BufError::BitsPerPixel(bpp) => write!(f, "{} bits per pixel is not supported", bpp),
+            BufError::TooSmall { expected, actual } => {
+                write!(f, "buffer is {} bytes, frame needs {}", actual, expected)
+            }
+            BufError::Stride { stride, row } => {
+                write!(
+                    f,
+                    "stride {} is shorter than a row of {} bytes",
+                    stride, row
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BufError {}
+
+#[derive(Debug)]
+pub struct RgbBuf {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+}
+
+impl RgbBuf {
+    pub fn new(
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+    ) -> Result<RgbBuf, BufError> {
+        let buf = RgbBuf {
+            pixels,
+            width,
+            height,
+            format,
+        };
+        buf.check()?;
+        Ok(buf)
+    }
+
+    // 0x0 Bgra8 without an allocation, to be filled by a grab
+    pub(crate) fn empty() -> RgbBuf {
+        RgbBuf {
+            pixels: Vec::new(),
+            width: 0,
+            height: 0,
+            format: PixelFormat::Bgra8,
+        }
+    }
+
+    pub fn len_for(width: u32, height: u32, format: PixelFormat) -> usize {
+        width as usize * height as usize * format.bytes_per_pixel()
+    }
+
+    pub fn as_view(&self) -> RgbBufView<'_> {
+        RgbBufView {
+            pixels: &self.pixels,
+            offset: 0,
+            width: self.width,
+            height: self.height,
+            stride: self.width as usize * self.format.bytes_per_pixel(),
+            format: self.format,
+        }
+    }
+
+    pub fn view(&self, region: Region) -> Result<RgbBufView<'_>, BufError> {
+        self.check()?;
+        self.as_view().view(region)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), BufError> {
+        let expected = RgbBuf::len_for(self.width, self.height, self.format);
+        match self.pixels.len() {
+            actual if actual != expected => Err(BufError::SizeMismatch { expected, actual }),
+            _ => Ok(()),
+        }
+    }
+
+    // rows into caller memory, stride bytes apart
+    pub fn copy_to_slice(&self, dst: &mut [u8], stride: usize) -> Result<(), BufError> {
+        self.check()?;
+        self.as_view().copy_to_slice(dst, stride)
+    }
+
+    pub fn convert(self, format: PixelFormat) -> Result<RgbBuf, BufError> {
+        self.check()?;
+        Ok(self.into_format(format))
+    }
+
+    // caller guarantees pixels.len() matches width, height and format
+    pub(crate) fn into_format(mut self, format: PixelFormat) -> RgbBuf {
+        let from = self.format;
+        match (from, format) {
+            (from, to) if from == to => {}
+            (PixelFormat::Bgra8, PixelFormat::Rgba8) | (PixelFormat::Rgba8, PixelFormat::Bgra8) => {
+                self.pixels.chunks_exact_mut(4).for_each(|c| c.swap(0, 2));
+            }
+            // fewer bytes per pixel, pixel i is written at or before where it was read
+            (from, to) if to.bytes_per_pixel() < from.bytes_per_pixel() => {
+                let (fb, tb) = (from.bytes_per_pixel(), to.bytes_per_pixel());
+                for i in 0..self.pixels.len() / fb {
+                    let px = from.read(&self.pixels[i * fb..(i + 1) * fb]);
+                    to.write(px, &mut self.pixels[i * tb..(i + 1) * tb]);
+                }
+                self.pixels
+                    .truncate(RgbBuf::len_for(self.width, self.height, to));
+            }
+            (from, to) => {
+                let mut pixels = vec![0; RgbBuf::len_for(self.width, self.height, to)];
+                self.pixels
+                    .chunks_exact(from.bytes_per_pixel())
+                    .zip(pixels.chunks_exact_mut(to.bytes_per_pixel()))
+                    .for_each(|(src, dst)| to.write(from.read(src), dst));
+                self.pixels = pixels;
+            }
+        }
+        self.format = format;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RgbBufView<'a> {
+    pub pixels: &'a [u8],
+    pub offset: usize,
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub format: PixelFormat,
+}
+
+impl<'a> RgbBufView<'a> {
+    pub fn new(
+        pixels: &'a [u8],
+        offset: usize,
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: PixelFormat,
+    ) -> Result<RgbBufView<'a>, BufError> {
+        let row = (width as usize).checked_mul(format.bytes_per_pixel());
+        let expected = match height {
+            0 => Some(offset),
+            h => row.and_then(|row| {
+                stride
+                    .checked_mul(h as usize - 1)?
+                    .checked_add(offset)?
+                    .checked_add(row)
+            }),
+        };
+        // sizes that overflow can't fit in any slice
+        let expected = expected.unwrap_or(usize::MAX);
+        if row.is_none_or(|row| stride < row) || pixels.len() < expected {
+            return Err(BufError::SizeMismatch {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        Ok(RgbBufView {
+            pixels,
+            offset,
+            width,
+            height,
+            stride,
+            format,
+        })
+    }
+
+    // the fields are public, so a view put together by hand may not fit its pixels
+    pub(crate) fn check(&self) -> Result<(), BufError> {
+        RgbBufView::new(
+            self.pixels,
+            self.offset,
+            self.width,
+            self.height,
+            self.stride,
+            self.format,
+        )
+        .map(drop)
+    }
+
+    pub fn view(&self, region: Region) -> Result<RgbBufView<'a>, BufError> {
+        self.check()?;
+        if !region.fits(self.width, self.height) {
+            return Err(BufError::OutOfBounds(region));
+        }
+        Ok(RgbBufView {
+            offset: self.offset
+                + region.y as usize * self.stride
+                + region.x as usize * self.format.bytes_per_pixel(),
+            width: region.width as u32,
+            height: region.height as u32,
+            ..*self
+        })
+    }
+
+    // stops at the first row that doesn't fit pixels instead of panicking
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let pixels = self.pixels;
+        let (offset, stride) = (self.offset, self.stride);
+        let row = (self.width as usize).checked_mul(self.format.bytes_per_pixel());
+        (0..self.height as usize).map_while(move |y| {
+            let start = stride.checked_mul(y)?.checked_add(offset)?;
+            pixels.get(start..start.checked_add(row?)?)
+        })
+    }
+
+    pub fn copy_to_slice(&self, dst: &mut [u8], stride: usize) -> Result<(), BufError> {
+        self.check()?;
+        let row = self.width as usize * self.format.bytes_per_pixel();
+        check_slice(dst.len(), stride, row, self.height)?;
+        for (y, line) in self.rows().enumerate() {
+            dst[y * stride..y * stride + row].copy_from_slice(line);
+        }
+        Ok(())
+    }
+
+    pub fn to_owned(&self) -> RgbBuf {
+        let mut pixels = Vec::with_capacity(RgbBuf::len_for(self.width, self.height, self.format));
+        self.rows().for_each(|r| pixels.extend_from_slice(r));
+        RgbBuf {
+            pixels,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+        }
+    }
+}
+
+// len bytes hold height rows of row bytes, stride bytes apart
+pub(crate) fn check_slice(
+    len: usize,
+    stride: usize,
+    row: usize,
+    height: u32,
+) -> Result<(), BufError> {
+    if stride < row {
+        return Err(BufError::Stride { stride, row });
+    }
+    // sizes that overflow can't fit in any slice
+    let expected = match height {
+        0 => Some(0),
+        h => stride
+            .checked_mul(h as usize - 1)
+            .and_then(|n| n.checked_add(row)),
+    }
+    .unwrap_or(usize::MAX);
+    match len < expected {
+        true => Err(BufError::TooSmall {
+            expected,
+            actual: len,
+        }),
+        false => Ok(()),
+    }
+}
@@ -1,54 +1,45 @@
-use std::ffi::c_void;
-use std::mem::size_of;
-use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, E_FAIL, HWND};
-use windows::Win32::Graphics::Gdi::{
-    BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits,
-    ReleaseDC, SelectObject, StretchBlt, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
-    SRCCOPY,
-};
-use windows::Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS, PW_CLIENTONLY};
-use windows::Win32::UI::HiDpi::{SetProcessDpiAwareness, PROCESS_PER_MONITOR_DPI_AWARE};
-use windows::Win32::UI::WindowsAndMessaging::{
-    GetSystemMetrics, PW_RENDERFULLCONTENT, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN,
-    SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
-};
+use crate::alpha::AlphaMode;
+pub use crate::buf::{PixelFormat, RgbBuf};
+use crate::error::Error;
+use crate::request::CaptureRequest;
 
-use crate::wrappers::{CreatedHdc, Hbitmap, Hdc, Rect};
-
-#[derive(Debug)]
-pub enum WSError {
-    GetDCIsNull,
-    GetClientRectIsZero,
-    CreateCompatibleDCIsNull,
-    CreateCompatibleBitmapIsNull,
-    SelectObjectError,
-    PrintWindowIsZero,
-    GetDIBitsError,
-    GetSystemMetricsIsZero,
-    StretchBltIsZero,
-    BitBltError,
-}
-
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Area {
     Full,
     ClientOnly,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Using {
     BitBlt,
     PrintWindow,
+    // BitBlt, PrintWindow when that comes out blank; the method that worked is
+    // remembered per target
+    Auto,
+    // X11 only: read windows from their XComposite pixmap, sees occluded and off-screen parts.
+    // Other backends fail window captures with Error::Unsupported
+    Composite,
 }
 
-#[derive(Debug)]
-pub struct RgbBuf {
-    pub pixels: Vec<u8>,
-    pub width: u32,
-    pub height: u32,
+#[derive(Clone, Copy)]
+pub struct Output {
+    pub format: PixelFormat,
+    // PrintWindow renders RTL layout windows mirrored, flip them back
+    pub unmirror: bool,
+    pub alpha: AlphaMode,
 }
 
-pub fn capture_window(hwnd: isize) -> Result<RgbBuf, windows::core::Error> {
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            format: PixelFormat::Rgba8,
+            unmirror: false,
+            alpha: AlphaMode::Opaque,
+        }
+    }
+}
+
+pub fn capture_window(hwnd: isize) -> Result<RgbBuf, Error> {
     capture_window_ex(hwnd, Using::PrintWindow, Area::Full, None, None)
 }
 
@@ -58,203 +49,92 @@ pub fn capture_window_ex(
     area: Area,
     crop_xy: Option<[i32; 2]>,
     crop_wh: Option<[i32; 2]>,
-) -> Result<RgbBuf, windows::core::Error> {
-    let hwnd = HWND(hwnd as *mut c_void);
-
-    unsafe {
-        #[allow(unused_must_use)]
-        {
-            SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE);
-        }
-
-        let hdc_screen = Hdc::get_dc(hwnd)?;
-
-        // BitBlt support only ClientOnly
-        let rect = match (using, area) {
-            (Using::PrintWindow, Area::Full) => Rect::get_window_rect(hwnd),
-            (Using::BitBlt, _) | (Using::PrintWindow, Area::ClientOnly) => {
-                Rect::get_client_rect(hwnd)
-            }
-        }?;
-
-        let [cx, cy] = crop_xy.unwrap_or([0, 0]);
-        let [cw, ch] = crop_wh.unwrap_or([rect.width - cx, rect.height - cy]);
-        let crop = crop_xy.is_some() || crop_wh.is_some();
-
-        let hdc = CreatedHdc::create_compatible_dc(hdc_screen.hdc)?;
-        let hbmp = match (crop, using) {
-            (true, Using::BitBlt) => Hbitmap::create_compatible_bitmap(hdc_screen.hdc, cw, ch),
-            (false, Using::BitBlt) | (_, Using::PrintWindow) => {
-                Hbitmap::create_compatible_bitmap(hdc_screen.hdc, rect.width, rect.height)
-            }
-        }?;
-
-        if SelectObject(hdc.hdc, hbmp.hbitmap.into()).is_invalid() {
-            return Err(windows::core::Error::from_thread());
-        }
-
-        let flags = PRINT_WINDOW_FLAGS(match area {
-            Area::Full => PW_RENDERFULLCONTENT,
-            Area::ClientOnly => PW_CLIENTONLY.0 | PW_RENDERFULLCONTENT,
-        });
-
-        match using {
-            Using::BitBlt => {
-                BitBlt(hdc.hdc, 0, 0, cw, ch, Some(hdc_screen.hdc), cx, cy, SRCCOPY)?;
-            }
-            Using::PrintWindow => {
-                if PrintWindow(hwnd, hdc.hdc, flags) == false {
-                    return Err(windows::core::Error::from_thread());
-                }
-            }
-        }
-
-        let (w, h, hdc, hbmp) = match (crop, using) {
-            (true, Using::PrintWindow) => {
-                let hdc2 = CreatedHdc::create_compatible_dc(hdc.hdc)?;
-                let hbmp2 = Hbitmap::create_compatible_bitmap(hdc.hdc, cw, ch)?;
-                let so = SelectObject(hdc2.hdc, hbmp2.hbitmap.into());
-                if so.is_invalid() {
-                    return Err(windows::core::Error::from_thread());
-                }
-                BitBlt(hdc2.hdc, 0, 0, cw, ch, Some(hdc.hdc), cx, cy, SRCCOPY)?;
-                if SelectObject(hdc2.hdc, so).is_invalid() {
-                    return Err(windows::core::Error::from_thread());
-                }
-                (cw, ch, hdc2, hbmp2)
-            }
-            (true, Using::BitBlt) => (cw, ch, hdc, hbmp),
-            (false, _) => (rect.width, rect.height, hdc, hbmp),
-        };
-
-        let bmih = BITMAPINFOHEADER {
-            biSize: size_of::<BITMAPINFOHEADER>() as u32,
-            biPlanes: 1,
-            biBitCount: 32,
-            biWidth: w,
-            biHeight: -h,
-            biCompression: BI_RGB.0 as u32,
-            ..Default::default()
-        };
-        let mut bmi = BITMAPINFO {
-            bmiHeader: bmih,
-            ..Default::default()
-        };
-        let mut buf: Vec<u8> = vec![0; (4 * w * h) as usize];
-        let gdb = GetDIBits(
-            hdc.hdc,
-            hbmp.hbitmap,
-            0,
-            h as u32,
-            Some(buf.as_mut_ptr() as *mut core::ffi::c_void),
-            &mut bmi,
-            DIB_RGB_COLORS,
-        );
-        if gdb == 0 || gdb == ERROR_INVALID_PARAMETER.0 as i32 {
-            return Err(windows::core::Error::new(E_FAIL, "GetDIBits error"));
-        }
-        buf.chunks_exact_mut(4).for_each(|c| c.swap(0, 2));
-        Ok(RgbBuf {
-            pixels: buf,
-            width: w as u32,
-            height: h as u32,
-        })
-    }
+) -> Result<RgbBuf, Error> {
+    capture_window_ex_with(hwnd, using, area, crop_xy, crop_wh, Output::default())
 }
 
-pub fn capture_display() -> Result<RgbBuf, WSError> {
-    unsafe {
-        // win 8.1 temporary DPI aware
-        #[allow(unused_must_use)]
-        {
-            SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE);
-        }
-        // for win 10
-        //SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
-        let hdc_screen = GetDC(None);
-        if hdc_screen.is_invalid() {
-            return Err(WSError::GetDCIsNull);
-        }
-
-        let hdc = CreateCompatibleDC(Some(hdc_screen));
-        if hdc.is_invalid() {
-            ReleaseDC(None, hdc_screen);
-            return Err(WSError::CreateCompatibleDCIsNull);
-        }
-
-        let x = GetSystemMetrics(SM_XVIRTUALSCREEN);
-        let y = GetSystemMetrics(SM_YVIRTUALSCREEN);
-        let width = GetSystemMetrics(SM_CXVIRTUALSCREEN);
-        let height = GetSystemMetrics(SM_CYVIRTUALSCREEN);
-
-        let hbmp = CreateCompatibleBitmap(hdc_screen, width, height);
-        if hbmp.is_invalid() {
-            let _ = DeleteDC(hdc);
-            ReleaseDC(None, hdc_screen);
-            return Err(WSError::CreateCompatibleBitmapIsNull);
-        }
-
-        let so = SelectObject(hdc, hbmp.into());
-        if so.is_invalid() {
-            let _ = DeleteDC(hdc);
-            let _ = DeleteObject(hbmp.into());
-            ReleaseDC(None, hdc_screen);
-            return Err(WSError::SelectObjectError);
-        }
+pub fn capture_window_ex_with(
+    hwnd: isize,
+    using: Using,
+    area: Area,
+    crop_xy: Option<[i32; 2]>,
+    crop_wh: Option<[i32; 2]>,
+    output: Output,
+) -> Result<RgbBuf, Error> {
+    CaptureRequest::window(hwnd)
+        .using(using)
+        .area(area)
+        .crop(crop_xy, crop_wh)
+        .output(output)
+        .capture()
+}
 
-        let sb = StretchBlt(
-            hdc, 0, 0, width, height, Some(hdc_screen), x, y, width, height, SRCCOPY,
-        );
-        if sb == false {
-            let _ = DeleteDC(hdc);
-            let _ = DeleteObject(hbmp.into());
-            ReleaseDC(None, hdc_screen);
-            return Err(WSError::StretchBltIsZero);
-        }
+// into caller memory of at least stride * (height - 1) + 4 * width bytes,
+// returns width and height
+pub fn capture_window_into(
+    hwnd: isize,
+    dst: &mut [u8],
+    stride: usize,
+) -> Result<(u32, u32), Error> {
+    capture_window_ex_into(
+        hwnd,
+        Using::PrintWindow,
+        Area::Full,
+        None,
+        None,
+        dst,
+        stride,
+    )
+}
 
-        let bmih = BITMAPINFOHEADER {
-            biSize: size_of::<BITMAPINFOHEADER>() as u32,
-            biPlanes: 1,
-            biBitCount: 32,
-            biWidth: width,
-            biHeight: -height,
-            biCompression: BI_RGB.0 as u32,
-            ..Default::default()
-        };
+pub fn capture_window_ex_into(
+    hwnd: isize,
+    using: Using,
+    area: Area,
+    crop_xy: Option<[i32; 2]>,
+    crop_wh: Option<[i32; 2]>,
+    dst: &mut [u8],
+    stride: usize,
+) -> Result<(u32, u32), Error> {
+    CaptureRequest::window(hwnd)
+        .using(using)
+        .area(area)
+        .crop(crop_xy, crop_wh)
+        .capture_to_slice(dst, stride)
+}
 
-        let mut bmi = BITMAPINFO {
-            bmiHeader: bmih,
-            ..Default::default()
-        };
+// reuses the allocation of buf
+pub fn capture_window_into_buf(hwnd: isize, buf: &mut RgbBuf) -> Result<(), Error> {
+    capture_window_ex_into_buf(hwnd, Using::PrintWindow, Area::Full, None, None, buf)
+}
 
-        let mut buf: Vec<u8> = vec![0; (4 * width * height) as usize];
+pub fn capture_window_ex_into_buf(
+    hwnd: isize,
+    using: Using,
+    area: Area,
+    crop_xy: Option<[i32; 2]>,
+    crop_wh: Option<[i32; 2]>,
+    buf: &mut RgbBuf,
+) -> Result<(), Error> {
+    CaptureRequest::window(hwnd)
+        .using(using)
+        .area(area)
+        .crop(crop_xy, crop_wh)
+        .capture_into(buf)
+}
 
-        let gdb = GetDIBits(
-            hdc,
-            hbmp,
-            0,
-            height as u32,
-            Some(buf.as_mut_ptr() as *mut core::ffi::c_void),
-            &mut bmi,
-            DIB_RGB_COLORS,
-        );
-        if gdb == 0 || gdb == ERROR_INVALID_PARAMETER.0 as i32 {
-            let _ = DeleteDC(hdc);
-            let _ = DeleteObject(hbmp.into());
-            ReleaseDC(None, hdc_screen);
-            return Err(WSError::GetDIBitsError);
-        }
+pub fn capture_display() -> Result<RgbBuf, Error> {
+    capture_display_with(Output::default())
+}
 
-        buf.chunks_exact_mut(4).for_each(|c| c.swap(0, 2));
+pub fn capture_display_with(output: Output) -> Result<RgbBuf, Error> {
+    CaptureRequest::display().output(output).capture()
+}
 
-        let _ = DeleteDC(hdc);
-        let _ = DeleteObject(hbmp.into());
-        ReleaseDC(None, hdc_screen);
+pub fn capture_display_into(dst: &mut [u8], stride: usize) -> Result<(u32, u32), Error> {
+    CaptureRequest::display().capture_to_slice(dst, stride)
+}
 
-        Ok(RgbBuf {
-            pixels: buf,
-            width: width as u32,
-            height: height as u32,
-        })
-    }
+pub fn capture_display_into_buf(buf: &mut RgbBuf) -> Result<(), Error> {
+    CaptureRequest::display().capture_into(buf)
 }
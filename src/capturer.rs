@@ -0,0 +1,86 @@
+use crate::backend::CaptureBackend;
+use crate::buf::RgbBuf;
+use crate::error::Error;
+#[cfg(windows)]
+use crate::gdi::{set_dpi_awareness, GdiBackend};
+use crate::pool::BufPool;
+use crate::request::CaptureRequest;
+#[cfg(all(unix, feature = "x11"))]
+use crate::x11::X11Backend;
+
+// one CaptureRequest captured over and over: the backend keeps its resources
+// (DCs and bitmap for GdiBackend) and frames land in reused buffers
+pub struct WindowCapturer<B> {
+    backend: B,
+    request: CaptureRequest,
+    frame: RgbBuf,
+}
+
+#[cfg(windows)]
+impl WindowCapturer<GdiBackend> {
+    pub fn new(request: CaptureRequest) -> WindowCapturer<GdiBackend> {
+        set_dpi_awareness(request.dpi);
+        WindowCapturer::with_backend(GdiBackend::new(request.using), request)
+    }
+}
+
+#[cfg(all(unix, feature = "x11"))]
+impl WindowCapturer<X11Backend> {
+    // $DISPLAY
+    pub fn new(request: CaptureRequest) -> Result<WindowCapturer<X11Backend>, Error> {
+        Ok(WindowCapturer::with_backend(
+            X11Backend::connect()?,
+            request,
+        ))
+    }
+}
+
+impl<B: CaptureBackend> WindowCapturer<B> {
+    // backend is switched to request.using
+    pub fn with_backend(mut backend: B, request: CaptureRequest) -> WindowCapturer<B> {
+        backend.set_using(request.using);
+        WindowCapturer {
+            backend,
+            request,
+            frame: RgbBuf::empty(),
+        }
+    }
+
+    // the frame stays in the capturer's own buffer until the next capture
+    pub fn capture(&mut self) -> Result<&RgbBuf, Error> {
+        self.request
+            .capture_into_with(&mut self.backend, &mut self.frame)?;
+        Ok(&self.frame)
+    }
+
+    // out is resized when the window size changes and reused otherwise
+    pub fn capture_into(&mut self, out: &mut RgbBuf) -> Result<(), Error> {
+        self.request.capture_into_with(&mut self.backend, out)
+    }
+
+    // straight into caller memory, see CaptureRequest::capture_to_slice_with
+    pub fn capture_to_slice(&mut self, dst: &mut [u8], stride: usize) -> Result<(u32, u32), Error> {
+        self.request
+            .capture_to_slice_scratch(&mut self.backend, dst, stride, &mut self.frame)
+    }
+
+    // frame in a buffer from pool, put it back when done with it
+    pub fn capture_pooled(&mut self, pool: &BufPool) -> Result<RgbBuf, Error> {
+        self.request.capture_pooled_with(&mut self.backend, pool)
+    }
+
+    // next Using::Auto capture starts over with the cheap method
+    pub fn forget_auto(&mut self) {
+        if let Some(auto) = self.backend.auto_methods() {
+            auto.forget(self.request.target);
+        }
+    }
+
+    pub fn request(&self) -> CaptureRequest {
+        self.request
+    }
+
+    pub fn backend(&mut self) -> &mut B {
+        &mut self.backend
+    }
+}
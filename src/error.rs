@@ -0,0 +1,242 @@
+use std::fmt;
+
+use crate::buf::BufError;
+use crate::request::Target;
+
+#[cfg(windows)]
+pub type OsError = windows::core::Error;
+#[cfg(not(windows))]
+pub type OsError = std::io::Error;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    GetDC(OsError),
+    GetRect(OsError),
+    CreateDC(OsError),
+    CreateBitmap(OsError),
+    SelectObject(OsError),
+    Blit(OsError),
+    PrintWindow(OsError),
+    GetDIBits(OsError),
+    GetSystemMetrics,
+    FindWindow(OsError),
+    EnumWindows(OsError),
+    TargetNotFound(Target),
+    // no capture implementation for this platform
+    Unsupported,
+    Buffer(BufError),
+    #[cfg(feature = "image")]
+    Image(image::ImageError),
+    #[cfg(all(unix, feature = "x11"))]
+    X11Connect(x11rb::errors::ConnectError),
+    #[cfg(all(unix, feature = "x11"))]
+    X11(x11rb::errors::ReplyOrIdError),
+    #[cfg(all(unix, feature = "x11"))]
+    Shm(OsError),
+    #[cfg(all(target_os = "linux", feature = "fbdev"))]
+    Fbdev(OsError),
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    WaylandConnect(wayland_client::ConnectError),
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    WaylandGlobals(wayland_client::globals::GlobalError),
+    // wl_shm or zwlr_screencopy_manager_v1 missing from the compositor
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    WaylandBind(wayland_client::globals::BindError),
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    WaylandDispatch(wayland_client::DispatchError),
+    // socket connect, memfd or reading the shm buffer
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    WaylandIo(OsError),
+    // compositor sent failed, or a buffer format that can't be converted
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    ScreencopyFailed,
+    // socket, handshake, authentication or protocol error talking to the server
+    #[cfg(feature = "vnc")]
+    Vnc(std::io::Error),
+    // capture thread of the async API exited or panicked
+    #[cfg(feature = "tokio")]
+    CaptureThread,
+    // FrameStream frame rate that isn't positive and finite
+    #[cfg(feature = "tokio")]
+    Fps(f64),
+}
+
+impl Error {
+    pub fn os_error(&self) -> Option<&OsError> {
+        match self {
+            Error::GetDC(e)
+            | Error::GetRect(e)
+            | Error::CreateDC(e)
+            | Error::CreateBitmap(e)
+            | Error::SelectObject(e)
+            | Error::Blit(e)
+            | Error::PrintWindow(e)
+            | Error::GetDIBits(e)
+            | Error::FindWindow(e)
+            | Error::EnumWindows(e) => Some(e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::Shm(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "fbdev"))]
+            Error::Fbdev(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandIo(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    // HRESULT of the failed call on windows, errno elsewhere
+    pub fn code(&self) -> Option<i32> {
+        #[cfg(windows)]
+        return self.os_error().map(|e| e.code().0);
+        #[cfg(not(windows))]
+        return self.os_error().and_then(|e| e.raw_os_error());
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self {
+            Error::GetDC(_) => "GetDC",
+            Error::GetRect(_) => "GetWindowRect/GetClientRect",
+            Error::CreateDC(_) => "CreateCompatibleDC",
+            Error::CreateBitmap(_) => "CreateCompatibleBitmap",
+            Error::SelectObject(_) => "SelectObject",
+            Error::Blit(_) => "BitBlt/StretchBlt",
+            Error::PrintWindow(_) => "PrintWindow",
+            Error::GetDIBits(_) => "GetDIBits",
+            Error::GetSystemMetrics => return write!(f, "GetSystemMetrics returned zero"),
+            Error::FindWindow(_) => "FindWindow",
+            Error::EnumWindows(_) => "EnumWindows",
+            Error::TargetNotFound(t) => return write!(f, "target {:?} not found", t),
+            Error::Unsupported => return write!(f, "capture is not supported on this platform"),
+            Error::Buffer(e) => return write!(f, "{}", e),
+            #[cfg(feature = "image")]
+            Error::Image(e) => return write!(f, "{}", e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::X11Connect(e) => return write!(f, "X11 connect failed: {}", e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::X11(e) => return write!(f, "X11 request failed: {}", e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::Shm(_) => "shmget/shmat",
+            #[cfg(all(target_os = "linux", feature = "fbdev"))]
+            Error::Fbdev(_) => "framebuffer",
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandConnect(e) => return write!(f, "Wayland connect failed: {}", e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandGlobals(e) => return write!(f, "Wayland registry failed: {}", e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandBind(e) => return write!(f, "Wayland global bind failed: {}", e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandDispatch(e) => return write!(f, "Wayland dispatch failed: {}", e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandIo(_) => "Wayland socket/shm",
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::ScreencopyFailed => "screencopy",
+            #[cfg(feature = "vnc")]
+            Error::Vnc(e) => return write!(f, "VNC failed: {}", e),
+            #[cfg(feature = "tokio")]
+            Error::CaptureThread => "capture thread",
+            #[cfg(feature = "tokio")]
+            Error::Fps(fps) => return write!(f, "fps must be positive, got {}", fps),
+        };
+        match self.os_error() {
+            Some(e) => write!(f, "{} failed: {}", stage, e),
+            None => write!(f, "{} failed", stage),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Buffer(e) => Some(e),
+            #[cfg(feature = "image")]
+            Error::Image(e) => Some(e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::X11Connect(e) => Some(e),
+            #[cfg(all(unix, feature = "x11"))]
+            Error::X11(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandConnect(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandGlobals(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandBind(e) => Some(e),
+            #[cfg(all(target_os = "linux", feature = "wayland"))]
+            Error::WaylandDispatch(e) => Some(e),
+            #[cfg(feature = "vnc")]
+            Error::Vnc(e) => Some(e),
+            e => e.os_error().map(|e| e as _),
+        }
+    }
+}
+
+impl From<BufError> for Error {
+    fn from(e: BufError) -> Self {
+        Error::Buffer(e)
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+#[cfg(all(unix, feature = "x11"))]
+impl From<x11rb::errors::ConnectError> for Error {
+    fn from(e: x11rb::errors::ConnectError) -> Self {
+        Error::X11Connect(e)
+    }
+}
+
+#[cfg(all(unix, feature = "x11"))]
+impl From<x11rb::errors::ConnectionError> for Error {
+    fn from(e: x11rb::errors::ConnectionError) -> Self {
+        Error::X11(e.into())
+    }
+}
+
+#[cfg(all(unix, feature = "x11"))]
+impl From<x11rb::errors::ReplyError> for Error {
+    fn from(e: x11rb::errors::ReplyError) -> Self {
+        Error::X11(e.into())
+    }
+}
+
+#[cfg(all(unix, feature = "x11"))]
+impl From<x11rb::errors::ReplyOrIdError> for Error {
+    fn from(e: x11rb::errors::ReplyOrIdError) -> Self {
+        Error::X11(e)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+impl From<wayland_client::ConnectError> for Error {
+    fn from(e: wayland_client::ConnectError) -> Self {
+        Error::WaylandConnect(e)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+impl From<wayland_client::globals::GlobalError> for Error {
+    fn from(e: wayland_client::globals::GlobalError) -> Self {
+        Error::WaylandGlobals(e)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+impl From<wayland_client::globals::BindError> for Error {
+    fn from(e: wayland_client::globals::BindError) -> Self {
+        Error::WaylandBind(e)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+impl From<wayland_client::DispatchError> for Error {
+    fn from(e: wayland_client::DispatchError) -> Self {
+        Error::WaylandDispatch(e)
+    }
+}
@@ -0,0 +1,223 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::RgbBuf;
+use crate::capture::Area;
+use crate::error::Error;
+use crate::packed::Packed;
+use crate::region::Region;
+use crate::request::Target;
+use crate::utils::HwndName;
+
+const FBIOGET_VSCREENINFO: u32 = 0x4600;
+const FBIOGET_FSCREENINFO: u32 = 0x4602;
+
+// struct fb_bitfield from linux/fb.h
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct FbBitfieldRaw {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+// struct fb_var_screeninfo from linux/fb.h
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+pub(crate) struct FbVarScreeninfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: FbBitfieldRaw,
+    green: FbBitfieldRaw,
+    blue: FbBitfieldRaw,
+    transp: FbBitfieldRaw,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    // pixclock through colorspace
+    timing: [u32; 11],
+    reserved: [u32; 4],
+}
+
+// struct fb_fix_screeninfo from linux/fb.h
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct FbFixScreeninfo {
+    id: [u8; 16],
+    smem_start: libc::c_ulong,
+    smem_len: u32,
+    type_: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: libc::c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FbBitfield {
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl FbBitfield {
+    fn mask(&self) -> u32 {
+        match self.length {
+            0 => 0,
+            l => (u32::MAX >> (32 - l.min(32))) << self.offset,
+        }
+    }
+}
+
+// what FBIOGET_VSCREENINFO and FBIOGET_FSCREENINFO report
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FbInfo {
+    pub width: u32,
+    pub height: u32,
+    // visible area inside the virtual framebuffer when panned
+    pub xoffset: u32,
+    pub yoffset: u32,
+    // 16, 24 or 32
+    pub bits_per_pixel: u32,
+    // bytes per line including padding
+    pub line_length: u32,
+    pub red: FbBitfield,
+    pub green: FbBitfield,
+    pub blue: FbBitfield,
+}
+
+impl FbInfo {
+    fn packed(&self) -> Packed {
+        Packed {
+            bits_per_pixel: self.bits_per_pixel,
+            red_mask: self.red.mask(),
+            green_mask: self.green.mask(),
+            blue_mask: self.blue.mask(),
+            big_endian: cfg!(target_endian = "big"),
+        }
+    }
+}
+
+// reads /dev/fb* or a file with the same layout, the whole screen is Target::Display
+pub struct FbdevBackend {
+    file: File,
+    info: FbInfo,
+    // screen info comes from the device and is queried again on every capture
+    device: bool,
+}
+
+impl FbdevBackend {
+    // framebuffer device like /dev/fb0
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FbdevBackend, Error> {
+        let file = File::open(path).map_err(Error::Fbdev)?;
+        let info = query(&file)?;
+        Ok(FbdevBackend {
+            file,
+            info,
+            device: true,
+        })
+    }
+
+    // any file holding framebuffer memory laid out as info says
+    pub fn with_info<P: AsRef<Path>>(path: P, info: FbInfo) -> Result<FbdevBackend, Error> {
+        let file = File::open(path).map_err(Error::Fbdev)?;
+        Ok(FbdevBackend {
+            file,
+            info,
+            device: false,
+        })
+    }
+
+    pub fn info(&self) -> FbInfo {
+        self.info
+    }
+}
+
+fn query(file: &File) -> Result<FbInfo, Error> {
+    let mut var = FbVarScreeninfo::default();
+    let mut fix = FbFixScreeninfo::default();
+    unsafe {
+        if libc::ioctl(file.as_raw_fd(), FBIOGET_VSCREENINFO as _, &mut var) == -1
+            || libc::ioctl(file.as_raw_fd(), FBIOGET_FSCREENINFO as _, &mut fix) == -1
+        {
+            return Err(Error::Fbdev(io::Error::last_os_error()));
+        }
+    }
+    let field = |f: &FbBitfieldRaw| FbBitfield {
+        offset: f.offset,
+        length: f.length,
+    };
+    Ok(FbInfo {
+        width: var.xres,
+        height: var.yres,
+        xoffset: var.xoffset,
+        yoffset: var.yoffset,
+        bits_per_pixel: var.bits_per_pixel,
+        line_length: fix.line_length,
+        red: field(&var.red),
+        green: field(&var.green),
+        blue: field(&var.blue),
+    })
+}
+
+impl CaptureBackend for FbdevBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn geometry(&mut self, target: Target, _area: Area) -> Result<Geometry, Error> {
+        if target != Target::Display {
+            return Err(Error::TargetNotFound(target));
+        }
+        if self.device {
+            self.info = query(&self.file)?;
+        }
+        Ok(Geometry {
+            width: self.info.width as i32,
+            height: self.info.height as i32,
+            mirrored: false,
+        })
+    }
+
+    fn grab(&mut self, target: Target, _area: Area, src: Region) -> Result<RgbBuf, Error> {
+        if target != Target::Display {
+            return Err(Error::TargetNotFound(target));
+        }
+        let info = self.info;
+        let bpp = info.bits_per_pixel as u64 / 8;
+        let line = info.line_length as u64;
+        let (x, y) = (
+            (info.xoffset + src.x as u32) as u64,
+            (info.yoffset + src.y as u32) as u64,
+        );
+        let (w, h) = (src.width as u32, src.height as u32);
+
+        // first pixel of the first row through the last pixel of the last row
+        let mut data = vec![0; ((h as u64 - 1) * line + w as u64 * bpp) as usize];
+        self.file
+            .read_exact_at(&mut data, y * line + x * bpp)
+            .map_err(Error::Fbdev)?;
+        Ok(info.packed().unpack(&data, w, h, line as usize)?)
+    }
+}
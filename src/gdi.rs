@@ -0,0 +1,517 @@
+use std::ffi::c_void;
+use std::mem::size_of;
+use windows::Win32::Foundation::{ERROR_INVALID_PARAMETER, E_FAIL, HWND, RECT};
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, GetDIBits, SelectObject, StretchBlt, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+    DIB_RGB_COLORS, HBITMAP, HDC, SRCCOPY,
+};
+use windows::Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS, PW_CLIENTONLY};
+use windows::Win32::UI::HiDpi::{SetProcessDpiAwareness, PROCESS_PER_MONITOR_DPI_AWARE};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetSystemMetrics, GetWindowLongW, GWL_EXSTYLE, PW_RENDERFULLCONTENT, SM_CXVIRTUALSCREEN,
+    SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, WS_EX_LAYOUTRTL,
+};
+
+use crate::auto::AutoMethods;
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::{PixelFormat, RgbBuf, RgbBufView};
+use crate::capture::{Area, Using};
+use crate::error::{Error, OsError};
+use crate::region::Region;
+use crate::request::{Dpi, Target};
+use crate::utils::{window_list, HwndName};
+use crate::wrappers::{CreatedHdc, Hbitmap, Hdc, Rect};
+
+pub struct BitBltBackend;
+
+pub struct PrintWindowBackend;
+
+// keeps the DCs and the bitmap between grabs and rebuilds them only when the
+// target or the bitmap size changes
+pub struct GdiBackend {
+    using: Using,
+    auto: AutoMethods,
+    cache: Option<GdiCache>,
+    // whole PrintWindow bitmap when only a part of it is wanted
+    scratch: Vec<u8>,
+}
+
+struct GdiCache {
+    target: Target,
+    width: i32,
+    height: i32,
+    // dropped in declaration order: memory DC, the bitmap selected into it, window DC
+    hdc: CreatedHdc,
+    hbmp: Hbitmap,
+    hdc_screen: Hdc,
+}
+
+impl CaptureBackend for BitBltBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        window_list()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        geometry(target, Using::BitBlt, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        grab(target, Using::BitBlt, area, src)
+    }
+}
+
+impl CaptureBackend for PrintWindowBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        window_list()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        geometry(target, Using::PrintWindow, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        grab(target, Using::PrintWindow, area, src)
+    }
+}
+
+impl GdiBackend {
+    pub fn new(using: Using) -> GdiBackend {
+        GdiBackend {
+            using,
+            // BitBlt only reaches the client area
+            auto: AutoMethods::default().full_area(Using::PrintWindow),
+            cache: None,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn using(&self) -> Using {
+        self.using
+    }
+
+    // DCs and bitmap for a width x height grab of target, reused when they match
+    unsafe fn cache(
+        &mut self,
+        target: Target,
+        hwnd: HWND,
+        width: i32,
+        height: i32,
+    ) -> Result<&GdiCache, Error> {
+        let reuse = matches!(&self.cache, Some(c) if (c.target, c.width, c.height) == (target, width, height));
+        if !reuse {
+            // release the old bitmap before creating a new one
+            self.cache = None;
+            let hdc_screen = Hdc::get_dc(hwnd).map_err(Error::GetDC)?;
+            let hdc = CreatedHdc::create_compatible_dc(hdc_screen.hdc).map_err(Error::CreateDC)?;
+            let hbmp = Hbitmap::create_compatible_bitmap(hdc_screen.hdc, width, height)
+                .map_err(Error::CreateBitmap)?;
+            if SelectObject(hdc.hdc, hbmp.hbitmap.into()).is_invalid() {
+                return Err(Error::SelectObject(OsError::from_thread()));
+            }
+            self.cache = Some(GdiCache {
+                target,
+                width,
+                height,
+                hdc,
+                hbmp,
+                hdc_screen,
+            });
+        }
+        Ok(self.cache.as_ref().expect("cache was just built"))
+    }
+
+    // draws target into the cached bitmap, returns the part of it holding src
+    unsafe fn render(&mut self, target: Target, area: Area, src: Region) -> Result<Region, Error> {
+        let hwnd = match target {
+            Target::Display => {
+                let rect = virtual_screen()?;
+                let c = self.cache(target, HWND::default(), src.width, src.height)?;
+                let sb = StretchBlt(
+                    c.hdc.hdc,
+                    0,
+                    0,
+                    src.width,
+                    src.height,
+                    Some(c.hdc_screen.hdc),
+                    rect.left + src.x,
+                    rect.top + src.y,
+                    src.width,
+                    src.height,
+                    SRCCOPY,
+                );
+                if sb == false {
+                    return Err(Error::Blit(OsError::from_thread()));
+                }
+                return Ok(Region::new(0, 0, src.width, src.height));
+            }
+            Target::Window(hwnd) => HWND(hwnd as *mut c_void),
+        };
+        match self.using {
+            // Auto is BitBlt until the request switches it over
+            Using::BitBlt | Using::Auto => {
+                let c = self.cache(target, hwnd, src.width, src.height)?;
+                BitBlt(
+                    c.hdc.hdc,
+                    0,
+                    0,
+                    src.width,
+                    src.height,
+                    Some(c.hdc_screen.hdc),
+                    src.x,
+                    src.y,
+                    SRCCOPY,
+                )
+                .map_err(Error::Blit)?;
+                Ok(Region::new(0, 0, src.width, src.height))
+            }
+            Using::PrintWindow => {
+                let rect = window_rect(hwnd, self.using, area)?;
+                let flags = PRINT_WINDOW_FLAGS(match area {
+                    Area::Full => PW_RENDERFULLCONTENT,
+                    Area::ClientOnly => PW_CLIENTONLY.0 | PW_RENDERFULLCONTENT,
+                });
+                let c = self.cache(target, hwnd, rect.width, rect.height)?;
+                if PrintWindow(hwnd, c.hdc.hdc, flags) == false {
+                    return Err(Error::PrintWindow(OsError::from_thread()));
+                }
+                Ok(src)
+            }
+            Using::Composite => Err(Error::Unsupported),
+        }
+    }
+
+    // whole cached bitmap into scratch, then f over the part of it
+    unsafe fn read_part(
+        &mut self,
+        part: Region,
+        f: impl FnOnce(RgbBufView) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let c = self.cache.as_ref().expect("render fills the cache");
+        let mut whole = RgbBuf {
+            pixels: std::mem::take(&mut self.scratch),
+            ..RgbBuf::empty()
+        };
+        let got = get_dibits_into(c.hdc.hdc, c.hbmp.hbitmap, c.width, c.height, &mut whole)
+            .and_then(|_| f(whole.view(part)?));
+        self.scratch = whole.pixels;
+        got
+    }
+
+    // the cached bitmap is the whole part
+    fn is_whole(&self, part: Region) -> bool {
+        let c = self.cache.as_ref().expect("render fills the cache");
+        part == Region::new(0, 0, c.width, c.height)
+    }
+}
+
+impl CaptureBackend for GdiBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        window_list()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        geometry(target, self.using, area)
+    }
+
+    fn set_using(&mut self, using: Using) {
+        self.using = using;
+    }
+
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        Some(&mut self.auto)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        let mut buf = RgbBuf::empty();
+        self.grab_into(target, area, src, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn grab_into(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        buf: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        unsafe {
+            let part = self.render(target, area, src)?;
+            if self.is_whole(part) {
+                let c = self.cache.as_ref().expect("render fills the cache");
+                return get_dibits_into(c.hdc.hdc, c.hbmp.hbitmap, c.width, c.height, buf);
+            }
+            self.read_part(part, |view| {
+                buf.pixels.clear();
+                view.rows().for_each(|r| buf.pixels.extend_from_slice(r));
+                (buf.width, buf.height, buf.format) = (view.width, view.height, view.format);
+                Ok(())
+            })
+        }
+    }
+
+    // GetDIBits writes straight into dst when the rows are packed
+    fn grab_to_slice(
+        &mut self,
+        target: Target,
+        area: Area,
+        src: Region,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(), Error> {
+        unsafe {
+            let part = self.render(target, area, src)?;
+            if self.is_whole(part) && stride == 4 * part.width as usize {
+                let c = self.cache.as_ref().expect("render fills the cache");
+                return get_dibits_raw(c.hdc.hdc, c.hbmp.hbitmap, c.width, c.height, dst);
+            }
+            self.read_part(part, |view| Ok(view.copy_to_slice(dst, stride)?))
+        }
+    }
+}
+
+pub(crate) fn set_dpi_awareness(dpi: Dpi) {
+    if dpi == Dpi::PerMonitorAware {
+        unsafe {
+            // win 8.1 temporary DPI aware
+            #[allow(unused_must_use)]
+            {
+                SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE);
+            }
+            // for win 10
+            //SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        }
+    }
+}
+
+// raw Bgra8 pixels of a selected bitmap
+unsafe fn get_dibits(hdc: &CreatedHdc, hbmp: &Hbitmap, w: i32, h: i32) -> Result<RgbBuf, Error> {
+    let mut buf = RgbBuf::empty();
+    get_dibits_into(hdc.hdc, hbmp.hbitmap, w, h, &mut buf)?;
+    Ok(buf)
+}
+
+// same into the allocation of buf
+unsafe fn get_dibits_into(
+    hdc: HDC,
+    hbmp: HBITMAP,
+    w: i32,
+    h: i32,
+    buf: &mut RgbBuf,
+) -> Result<(), Error> {
+    buf.pixels.resize((4 * w * h) as usize, 0);
+    get_dibits_raw(hdc, hbmp, w, h, &mut buf.pixels)?;
+    (buf.width, buf.height, buf.format) = (w as u32, h as u32, PixelFormat::Bgra8);
+    Ok(())
+}
+
+// packed rows into dst, which holds at least 4 * w * h bytes
+unsafe fn get_dibits_raw(
+    hdc: HDC,
+    hbmp: HBITMAP,
+    w: i32,
+    h: i32,
+    dst: &mut [u8],
+) -> Result<(), Error> {
+    debug_assert!(dst.len() >= (4 * w * h) as usize);
+    let bmih = BITMAPINFOHEADER {
+        biSize: size_of::<BITMAPINFOHEADER>() as u32,
+        biPlanes: 1,
+        biBitCount: 32,
+        biWidth: w,
+        biHeight: -h,
+        biCompression: BI_RGB.0,
+        ..Default::default()
+    };
+    let mut bmi = BITMAPINFO {
+        bmiHeader: bmih,
+        ..Default::default()
+    };
+    let gdb = GetDIBits(
+        hdc,
+        hbmp,
+        0,
+        h as u32,
+        Some(dst.as_mut_ptr() as *mut core::ffi::c_void),
+        &mut bmi,
+        DIB_RGB_COLORS,
+    );
+    if gdb == 0 || gdb == ERROR_INVALID_PARAMETER.0 as i32 {
+        return Err(Error::GetDIBits(OsError::new(E_FAIL, "GetDIBits error")));
+    }
+    Ok(())
+}
+
+fn virtual_screen() -> Result<Rect, Error> {
+    let (x, y, width, height) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+    match width == 0 || height == 0 {
+        true => Err(Error::GetSystemMetrics),
+        false => Ok(Rect::from(RECT {
+            left: x,
+            top: y,
+            right: x + width,
+            bottom: y + height,
+        })),
+    }
+}
+
+fn window_rect(hwnd: HWND, using: Using, area: Area) -> Result<Rect, Error> {
+    // BitBlt support only ClientOnly
+    match (using, area) {
+        (Using::PrintWindow, Area::Full) => Rect::get_window_rect(hwnd),
+        (Using::BitBlt | Using::Auto, _) | (Using::PrintWindow, Area::ClientOnly) => {
+            Rect::get_client_rect(hwnd)
+        }
+        (Using::Composite, _) => return Err(Error::Unsupported),
+    }
+    .map_err(Error::GetRect)
+}
+
+fn geometry(target: Target, using: Using, area: Area) -> Result<Geometry, Error> {
+    let hwnd = match target {
+        Target::Display => {
+            let rect = virtual_screen()?;
+            return Ok(Geometry {
+                width: rect.width,
+                height: rect.height,
+                mirrored: false,
+            });
+        }
+        Target::Window(hwnd) => HWND(hwnd as *mut c_void),
+    };
+    let rect = window_rect(hwnd, using, area)?;
+    // PrintWindow renders RTL layout windows mirrored
+    let mirrored = using == Using::PrintWindow
+        && unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } as u32 & WS_EX_LAYOUTRTL.0 != 0;
+    Ok(Geometry {
+        width: rect.width,
+        height: rect.height,
+        mirrored,
+    })
+}
+
+fn grab(target: Target, using: Using, area: Area, src: Region) -> Result<RgbBuf, Error> {
+    match target {
+        Target::Display => {
+            let rect = virtual_screen()?;
+            unsafe {
+                grab_display_region(rect.left + src.x, rect.top + src.y, src.width, src.height)
+            }
+        }
+        Target::Window(hwnd) => {
+            let hwnd = HWND(hwnd as *mut c_void);
+            let rect = window_rect(hwnd, using, area)?;
+            unsafe { grab_window_region(hwnd, using, area, &rect, src) }
+        }
+    }
+}
+
+unsafe fn grab_window_region(
+    hwnd: HWND,
+    using: Using,
+    area: Area,
+    rect: &Rect,
+    src: Region,
+) -> Result<RgbBuf, Error> {
+    let Region {
+        x: cx,
+        y: cy,
+        width: cw,
+        height: ch,
+    } = src;
+    let crop = src != Region::new(0, 0, rect.width, rect.height);
+
+    let hdc_screen = Hdc::get_dc(hwnd).map_err(Error::GetDC)?;
+
+    let hdc = CreatedHdc::create_compatible_dc(hdc_screen.hdc).map_err(Error::CreateDC)?;
+    let hbmp = match (crop, using) {
+        (true, Using::BitBlt | Using::Auto) => {
+            Hbitmap::create_compatible_bitmap(hdc_screen.hdc, cw, ch)
+        }
+        (false, Using::BitBlt | Using::Auto) | (_, Using::PrintWindow) => {
+            Hbitmap::create_compatible_bitmap(hdc_screen.hdc, rect.width, rect.height)
+        }
+        (_, Using::Composite) => return Err(Error::Unsupported),
+    }
+    .map_err(Error::CreateBitmap)?;
+
+    if SelectObject(hdc.hdc, hbmp.hbitmap.into()).is_invalid() {
+        return Err(Error::SelectObject(OsError::from_thread()));
+    }
+
+    let flags = PRINT_WINDOW_FLAGS(match area {
+        Area::Full => PW_RENDERFULLCONTENT,
+        Area::ClientOnly => PW_CLIENTONLY.0 | PW_RENDERFULLCONTENT,
+    });
+
+    match using {
+        Using::BitBlt | Using::Auto => {
+            BitBlt(hdc.hdc, 0, 0, cw, ch, Some(hdc_screen.hdc), cx, cy, SRCCOPY)
+                .map_err(Error::Blit)?;
+        }
+        Using::PrintWindow => {
+            if PrintWindow(hwnd, hdc.hdc, flags) == false {
+                return Err(Error::PrintWindow(OsError::from_thread()));
+            }
+        }
+        Using::Composite => return Err(Error::Unsupported),
+    }
+
+    match (crop, using) {
+        (true, Using::PrintWindow) => {
+            let hdc2 = CreatedHdc::create_compatible_dc(hdc.hdc).map_err(Error::CreateDC)?;
+            let hbmp2 =
+                Hbitmap::create_compatible_bitmap(hdc.hdc, cw, ch).map_err(Error::CreateBitmap)?;
+            let so = SelectObject(hdc2.hdc, hbmp2.hbitmap.into());
+            if so.is_invalid() {
+                return Err(Error::SelectObject(OsError::from_thread()));
+            }
+            BitBlt(hdc2.hdc, 0, 0, cw, ch, Some(hdc.hdc), cx, cy, SRCCOPY).map_err(Error::Blit)?;
+            if SelectObject(hdc2.hdc, so).is_invalid() {
+                return Err(Error::SelectObject(OsError::from_thread()));
+            }
+            get_dibits(&hdc2, &hbmp2, cw, ch)
+        }
+        (true, Using::BitBlt | Using::Auto) => get_dibits(&hdc, &hbmp, cw, ch),
+        (true, Using::Composite) => Err(Error::Unsupported),
+        (false, _) => get_dibits(&hdc, &hbmp, rect.width, rect.height),
+    }
+}
+
+unsafe fn grab_display_region(x: i32, y: i32, width: i32, height: i32) -> Result<RgbBuf, Error> {
+    let hdc_screen = Hdc::get_dc(HWND::default()).map_err(Error::GetDC)?;
+
+    let hbmp = Hbitmap::create_compatible_bitmap(hdc_screen.hdc, width, height)
+        .map_err(Error::CreateBitmap)?;
+    // declared after hbmp so it is dropped first and releases the bitmap
+    let hdc = CreatedHdc::create_compatible_dc(hdc_screen.hdc).map_err(Error::CreateDC)?;
+
+    if SelectObject(hdc.hdc, hbmp.hbitmap.into()).is_invalid() {
+        return Err(Error::SelectObject(OsError::from_thread()));
+    }
+
+    let sb = StretchBlt(
+        hdc.hdc,
+        0,
+        0,
+        width,
+        height,
+        Some(hdc_screen.hdc),
+        x,
+        y,
+        width,
+        height,
+        SRCCOPY,
+    );
+    if sb == false {
+        return Err(Error::Blit(OsError::from_thread()));
+    }
+
+    get_dibits(&hdc, &hbmp, width, height)
+}
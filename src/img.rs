@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use image::{DynamicImage, ExtendedColorType, GrayImage, ImageFormat, RgbImage, RgbaImage};
+
+use crate::buf::{BufError, PixelFormat, RgbBuf};
+use crate::error::Error;
+
+fn from_raw<I>(
+    buf: RgbBuf,
+    format: PixelFormat,
+    f: fn(u32, u32, Vec<u8>) -> Option<I>,
+) -> Result<I, BufError> {
+    let buf = buf.convert(format)?;
+    // size already checked by convert
+    Ok(f(buf.width, buf.height, buf.pixels).expect("pixel buffer size"))
+}
+
+impl TryFrom<RgbBuf> for RgbaImage {
+    type Error = BufError;
+
+    fn try_from(buf: RgbBuf) -> Result<Self, Self::Error> {
+        from_raw(buf, PixelFormat::Rgba8, RgbaImage::from_raw)
+    }
+}
+
+impl TryFrom<RgbBuf> for RgbImage {
+    type Error = BufError;
+
+    fn try_from(buf: RgbBuf) -> Result<Self, Self::Error> {
+        from_raw(buf, PixelFormat::Rgb8, RgbImage::from_raw)
+    }
+}
+
+impl TryFrom<RgbBuf> for DynamicImage {
+    type Error = BufError;
+
+    fn try_from(buf: RgbBuf) -> Result<Self, Self::Error> {
+        Ok(match buf.format {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => {
+                DynamicImage::ImageRgba8(RgbaImage::try_from(buf)?)
+            }
+            PixelFormat::Rgb8 => DynamicImage::ImageRgb8(RgbImage::try_from(buf)?),
+            PixelFormat::Gray8 => {
+                DynamicImage::ImageLuma8(from_raw(buf, PixelFormat::Gray8, GrayImage::from_raw)?)
+            }
+        })
+    }
+}
+
+impl From<RgbaImage> for RgbBuf {
+    fn from(img: RgbaImage) -> Self {
+        let (width, height) = img.dimensions();
+        RgbBuf {
+            pixels: img.into_raw(),
+            width,
+            height,
+            format: PixelFormat::Rgba8,
+        }
+    }
+}
+
+impl From<RgbImage> for RgbBuf {
+    fn from(img: RgbImage) -> Self {
+        let (width, height) = img.dimensions();
+        RgbBuf {
+            pixels: img.into_raw(),
+            width,
+            height,
+            format: PixelFormat::Rgb8,
+        }
+    }
+}
+
+impl From<GrayImage> for RgbBuf {
+    fn from(img: GrayImage) -> Self {
+        let (width, height) = img.dimensions();
+        RgbBuf {
+            pixels: img.into_raw(),
+            width,
+            height,
+            format: PixelFormat::Gray8,
+        }
+    }
+}
+
+impl From<DynamicImage> for RgbBuf {
+    fn from(img: DynamicImage) -> Self {
+        match img {
+            DynamicImage::ImageRgba8(img) => img.into(),
+            DynamicImage::ImageRgb8(img) => img.into(),
+            DynamicImage::ImageLuma8(img) => img.into(),
+            img => img.into_rgba8().into(),
+        }
+    }
+}
+
+impl RgbBuf {
+    // encoder is picked from the path extension, jpeg drops the alpha channel
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.check()?;
+        let path = path.as_ref();
+        let target = match (ImageFormat::from_path(path)?, self.format) {
+            (ImageFormat::Jpeg, PixelFormat::Bgra8 | PixelFormat::Rgba8) => PixelFormat::Rgb8,
+            (_, PixelFormat::Bgra8) => PixelFormat::Rgba8,
+            (_, format) => format,
+        };
+        let color = match target {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => ExtendedColorType::Rgba8,
+            PixelFormat::Rgb8 => ExtendedColorType::Rgb8,
+            PixelFormat::Gray8 => ExtendedColorType::L8,
+        };
+        let converted;
+        let pixels = match target == self.format {
+            true => &self.pixels,
+            false => {
+                converted = RgbBuf {
+                    pixels: self.pixels.clone(),
+                    ..*self
+                }
+                .into_format(target);
+                &converted.pixels
+            }
+        };
+        Ok(image::save_buffer(
+            path,
+            pixels,
+            self.width,
+            self.height,
+            color,
+        )?)
+    }
+}
@@ -5,9 +5,48 @@
 // https://stackoverflow.com/questions/36261725/how-to-extract-a-part-of-hbitmap-without-using-bitblt
 // https://stackoverflow.com/questions/3671008/crop-function-bitblt
 
+pub mod alpha;
+#[cfg(feature = "tokio")]
+pub mod async_capture;
+pub mod auto;
+pub mod backend;
+pub mod buf;
 pub mod capture;
+pub mod capturer;
+pub mod error;
+#[cfg(all(target_os = "linux", feature = "fbdev"))]
+pub mod fbdev;
+#[cfg(windows)]
+pub mod gdi;
+#[cfg(feature = "image")]
+mod img;
+pub mod mock;
+#[cfg(any(
+    all(unix, feature = "x11"),
+    all(target_os = "linux", feature = "fbdev"),
+    all(target_os = "linux", feature = "wayland")
+))]
+mod packed;
+pub mod pool;
 pub mod prelude;
+pub mod region;
+pub mod request;
+pub mod resize;
+#[cfg(feature = "vnc")]
+mod rfb;
+pub mod stream;
 #[cfg(test)]
 mod tests;
+mod transform;
 pub mod utils;
+#[cfg(feature = "vnc")]
+pub mod vnc;
+#[cfg(feature = "vnc")]
+pub mod vnc_server;
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+pub mod wayland;
+#[cfg(windows)]
 mod wrappers;
+#[cfg(all(unix, feature = "x11"))]
+pub mod x11;
+pub mod yuv;
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::auto::AutoMethods;
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::capture::{Area, Using};
+use crate::error::Error;
+use crate::region::Region;
+use crate::request::Target;
+use crate::utils::HwndName;
+
+// In-memory backend serving scripted frames, the last frame of a target repeats
+#[derive(Default)]
+pub struct MockBackend {
+    windows: Vec<(isize, String)>,
+    frames: HashMap<Target, VecDeque<RgbBuf>>,
+    mirrored: Vec<Target>,
+    black: Vec<(Target, Using)>,
+    using: Option<Using>,
+    auto: AutoMethods,
+    grabs: usize,
+}
+
+impl MockBackend {
+    pub fn new() -> MockBackend {
+        MockBackend::default()
+    }
+
+    pub fn add_window(&mut self, hwnd: isize, name: &str) -> &mut MockBackend {
+        self.windows.push((hwnd, name.to_string()));
+        self
+    }
+
+    pub fn push_frame(&mut self, target: Target, frame: RgbBuf) -> &mut MockBackend {
+        self.frames.entry(target).or_default().push_back(frame);
+        self
+    }
+
+    // report target as mirrored like an RTL window under PrintWindow
+    pub fn set_mirrored(&mut self, target: Target) -> &mut MockBackend {
+        self.mirrored.push(target);
+        self
+    }
+
+    // grabs of target come out black while using is set, like BitBlt of a GPU window
+    pub fn black_with(&mut self, target: Target, using: Using) -> &mut MockBackend {
+        self.black.push((target, using));
+        self
+    }
+
+    // Using::Auto state, the GDI pair by default
+    pub fn set_auto(&mut self, auto: AutoMethods) -> &mut MockBackend {
+        self.auto = auto;
+        self
+    }
+
+    // last set_using, None until Using::Auto asked for a method
+    pub fn using(&self) -> Option<Using> {
+        self.using
+    }
+
+    pub fn grabs(&self) -> usize {
+        self.grabs
+    }
+
+    // count the grab and move on to the next scripted frame
+    fn next(&mut self, target: Target) {
+        let queue = self.frames.get_mut(&target).expect("frame exists");
+        if queue.len() > 1 {
+            queue.pop_front();
+        }
+        self.grabs += 1;
+    }
+
+    fn frame(&self, target: Target) -> Result<&RgbBuf, Error> {
+        self.frames
+            .get(&target)
+            .and_then(|f| f.front())
+            .ok_or(Error::TargetNotFound(target))
+    }
+
+    fn is_black(&self, target: Target) -> bool {
+        matches!(self.using, Some(u) if self.black.contains(&(target, u)))
+    }
+}
+
+impl CaptureBackend for MockBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        Ok(self
+            .windows
+            .iter()
+            .map(|(hwnd, name)| HwndName {
+                hwnd: *hwnd,
+                window_name: name.clone(),
+            })
+            .collect())
+    }
+
+    fn geometry(&mut self, target: Target, _area: Area) -> Result<Geometry, Error> {
+        let frame = self.frame(target)?;
+        Ok(Geometry {
+            width: frame.width as i32,
+            height: frame.height as i32,
+            mirrored: self.mirrored.contains(&target),
+        })
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        let mut buf = RgbBuf::empty();
+        self.grab_into(target, area, src, &mut buf)?;
+        Ok(buf)
+    }
+
+    // copies into the existing allocation like a backend with a persistent bitmap
+    fn grab_into(
+        &mut self,
+        target: Target,
+        _area: Area,
+        src: Region,
+        buf: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        let view = self.frame(target)?.view(src)?;
+        buf.pixels.clear();
+        view.rows().for_each(|r| buf.pixels.extend_from_slice(r));
+        (buf.width, buf.height, buf.format) = (view.width, view.height, view.format);
+        if self.is_black(target) {
+            buf.pixels.fill(0);
+        }
+        self.next(target);
+        Ok(())
+    }
+
+    fn grab_to_slice(
+        &mut self,
+        target: Target,
+        _area: Area,
+        src: Region,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(), Error> {
+        let view = self.frame(target)?.view(src)?;
+        let c = view.format.bytes_per_pixel();
+        for (y, line) in view.rows().enumerate() {
+            let row = &mut dst[y * stride..y * stride + view.width as usize * 4];
+            for (s, d) in line.chunks_exact(c).zip(row.chunks_exact_mut(4)) {
+                PixelFormat::Bgra8.write(view.format.read(s), d);
+            }
+        }
+        if self.is_black(target) {
+            dst.chunks_mut(stride)
+                .take(view.height as usize)
+                .for_each(|r| r[..view.width as usize * 4].fill(0));
+        }
+        self.next(target);
+        Ok(())
+    }
+
+    fn set_using(&mut self, using: Using) {
+        self.using = Some(using);
+    }
+
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        Some(&mut self.auto)
+    }
+}
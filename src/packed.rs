@@ -0,0 +1,105 @@
+use crate::buf::{BufError, PixelFormat, RgbBuf};
+
+// packed truecolor pixel layout, as X11 visuals and fbdev describe it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Packed {
+    pub bits_per_pixel: u32,
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+    pub big_endian: bool,
+}
+
+impl Packed {
+    // row length in bytes, rows are padded to scanline_pad bits
+    #[cfg(all(unix, feature = "x11"))]
+    pub fn stride(&self, width: u32, scanline_pad: u32) -> usize {
+        let pad = scanline_pad.max(8) as usize;
+        (width as usize * self.bits_per_pixel as usize).div_ceil(pad) * pad / 8
+    }
+
+    // data may be longer than height rows, the rest is ignored
+    pub fn unpack(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<RgbBuf, BufError> {
+        let bpp = match self.bits_per_pixel {
+            8 | 16 | 24 | 32 => self.bits_per_pixel as usize / 8,
+            b => return Err(BufError::BitsPerPixel(b)),
+        };
+        let row = width as usize * bpp;
+        // sizes that overflow can't fit in any slice
+        let expected = match height {
+            0 => Some(0),
+            h => stride
+                .checked_mul(h as usize - 1)
+                .and_then(|n| n.checked_add(row)),
+        }
+        .unwrap_or(usize::MAX);
+        if data.len() < expected || stride < row {
+            return Err(BufError::SizeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let mut pixels = Vec::with_capacity(RgbBuf::len_for(width, height, PixelFormat::Bgra8));
+        for line in data.chunks(stride.max(1)).take(height as usize) {
+            let line = &line[..row];
+            if self.is_bgrx() {
+                // native layout of most X servers and framebuffers
+                for px in line.chunks_exact(4) {
+                    pixels.extend_from_slice(&[px[0], px[1], px[2], 255]);
+                }
+                continue;
+            }
+            for px in line.chunks_exact(bpp) {
+                let v = match self.big_endian {
+                    true => px.iter().fold(0u32, |v, &b| v << 8 | b as u32),
+                    false => px.iter().rev().fold(0u32, |v, &b| v << 8 | b as u32),
+                };
+                pixels.extend_from_slice(&[
+                    channel(v, self.blue_mask),
+                    channel(v, self.green_mask),
+                    channel(v, self.red_mask),
+                    255,
+                ]);
+            }
+        }
+
+        Ok(RgbBuf {
+            pixels,
+            width,
+            height,
+            format: PixelFormat::Bgra8,
+        })
+    }
+
+    fn is_bgrx(&self) -> bool {
+        self.bits_per_pixel == 32
+            && !self.big_endian
+            && self.red_mask == 0xff0000
+            && self.green_mask == 0xff00
+            && self.blue_mask == 0xff
+    }
+}
+
+// scale a masked channel to 8 bits
+fn channel(v: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let bits = (mask >> shift).trailing_ones();
+    let c = (v & mask) >> shift;
+    match bits {
+        8.. => (c >> (bits - 8)) as u8,
+        _ => {
+            let max = (1 << bits) - 1;
+            ((c * 255 + max / 2) / max) as u8
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use crate::buf::RgbBuf;
+
+// recycles frame allocations; clones share the same buffers, so frames handed
+// to another thread can come back from there
+#[derive(Clone)]
+pub struct BufPool {
+    free: Arc<Mutex<Vec<Vec<u8>>>>,
+    // buffers kept at most, more are dropped on put
+    max: usize,
+}
+
+// enough for a frame being filled, one being shown and one in flight between them
+impl Default for BufPool {
+    fn default() -> Self {
+        BufPool::new(3)
+    }
+}
+
+impl BufPool {
+    pub fn new(max: usize) -> BufPool {
+        BufPool {
+            free: Arc::default(),
+            max,
+        }
+    }
+
+    // empty frame on a recycled allocation when one is free
+    pub fn take(&self) -> RgbBuf {
+        let mut pixels = self.free().pop().unwrap_or_default();
+        pixels.clear();
+        RgbBuf {
+            pixels,
+            ..RgbBuf::empty()
+        }
+    }
+
+    pub fn put(&self, buf: RgbBuf) {
+        let mut free = self.free();
+        if free.len() < self.max {
+            free.push(buf.pixels);
+        }
+    }
+
+    // free buffers
+    pub fn len(&self) -> usize {
+        self.free().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn free(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // a panic elsewhere can't leave the list half updated
+        self.free.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -1,4 +1,42 @@
 pub use super::{
-    capture::{capture_display, capture_window, capture_window_ex, Area, RgbBuf, Using, WSError},
-    utils::{find_window, window_list, FWError, HwndName, WLError},
+    alpha::AlphaMode,
+    auto::{auto_method, forget_auto, AutoMethods},
+    backend::{CaptureBackend, Geometry},
+    buf::{BufError, PixelFormat, RgbBuf, RgbBufView},
+    capture::{
+        capture_display, capture_display_into, capture_display_into_buf, capture_display_with,
+        capture_window, capture_window_ex, capture_window_ex_into, capture_window_ex_into_buf,
+        capture_window_ex_with, capture_window_into, capture_window_into_buf, Area, Output, Using,
+    },
+    capturer::WindowCapturer,
+    error::Error,
+    mock::MockBackend,
+    pool::BufPool,
+    region::{Fit, Region, RegionPolicy},
+    request::{CaptureRequest, Dpi, Target},
+    resize::Filter,
+    stream::{CaptureStream, Clock, FakeClock, Frame, SystemClock},
+    utils::{find_window, window_list, HwndName},
+    yuv::{ColorMatrix, ColorRange, YuvFrame, YuvLayout},
+};
+
+#[cfg(windows)]
+pub use super::gdi::{BitBltBackend, GdiBackend, PrintWindowBackend};
+
+#[cfg(all(unix, feature = "x11"))]
+pub use super::x11::{X11Backend, X11Capturer};
+
+#[cfg(all(target_os = "linux", feature = "fbdev"))]
+pub use super::fbdev::{FbBitfield, FbInfo, FbdevBackend};
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+pub use super::wayland::WaylandBackend;
+
+#[cfg(feature = "vnc")]
+pub use super::{vnc::VncBackend, vnc_server::VncServer};
+
+#[cfg(feature = "tokio")]
+pub use super::async_capture::{
+    capture_display_async, capture_window_async, capture_window_ex_async, AsyncCapturer,
+    FrameStream,
 };
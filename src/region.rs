@@ -0,0 +1,127 @@
+use crate::buf::{BufError, RgbBuf};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub(crate) fn fits(&self, width: u32, height: u32) -> bool {
+        self.x >= 0
+            && self.y >= 0
+            && self.width >= 0
+            && self.height >= 0
+            && self.x as i64 + self.width as i64 <= width as i64
+            && self.y as i64 + self.height as i64 <= height as i64
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RegionPolicy {
+    // shrink the region to the part inside the source
+    Clamp,
+    // fail with BufError::OutOfBounds unless the region is fully inside
+    Error,
+    // keep the requested size, fill the part outside the source with RGBA color
+    PadWithColor([u8; 4]),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Fit {
+    // part of the source that is read, in source coordinates
+    pub src: Region,
+    // where src lands in the output
+    pub dst: Region,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    // crop_xy defaults to 0,0 and crop_wh to the rest of the source
+    pub fn from_crop(
+        crop_xy: Option<[i32; 2]>,
+        crop_wh: Option<[i32; 2]>,
+        width: i32,
+        height: i32,
+    ) -> Region {
+        let [x, y] = crop_xy.unwrap_or([0, 0]);
+        let [w, h] = crop_wh.unwrap_or([width - x, height - y]);
+        Region::new(x, y, w, h)
+    }
+
+    pub fn intersect(&self, other: &Region) -> Option<Region> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x as i64 + self.width as i64).min(other.x as i64 + other.width as i64);
+        let bottom = (self.y as i64 + self.height as i64).min(other.y as i64 + other.height as i64);
+        match (right - x as i64, bottom - y as i64) {
+            (w, h) if w > 0 && h > 0 => Some(Region::new(x, y, w as i32, h as i32)),
+            _ => None,
+        }
+    }
+
+    pub fn fit(&self, width: i32, height: i32, policy: RegionPolicy) -> Result<Fit, BufError> {
+        let out_of_bounds = Err(BufError::OutOfBounds(*self));
+        if self.width <= 0 || self.height <= 0 {
+            return out_of_bounds;
+        }
+        let src = self.intersect(&Region::new(0, 0, width, height));
+        match (policy, src) {
+            (RegionPolicy::Error, Some(src)) if src != *self => out_of_bounds,
+            (RegionPolicy::Clamp | RegionPolicy::Error, Some(src)) => Ok(Fit {
+                src,
+                dst: Region::new(0, 0, src.width, src.height),
+                width: src.width,
+                height: src.height,
+            }),
+            (RegionPolicy::PadWithColor(_), src) => {
+                let src = src.unwrap_or(Region::new(0, 0, 0, 0));
+                Ok(Fit {
+                    src,
+                    dst: Region::new(src.x - self.x, src.y - self.y, src.width, src.height),
+                    width: self.width,
+                    height: self.height,
+                })
+            }
+            _ => out_of_bounds,
+        }
+    }
+}
+
+impl Fit {
+    // put src pixels at dst on a canvas of the fitted size
+    pub(crate) fn place(&self, buf: RgbBuf, color: [u8; 4]) -> RgbBuf {
+        if self.dst == Region::new(0, 0, self.width, self.height) {
+            return buf;
+        }
+        let c = buf.format.bytes_per_pixel();
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut pixels = vec![0; RgbBuf::len_for(width, height, buf.format)];
+        pixels
+            .chunks_exact_mut(c)
+            .for_each(|px| buf.format.write(color, px));
+        let row = width as usize * c;
+        for (y, line) in buf.as_view().rows().enumerate() {
+            let start = (self.dst.y as usize + y) * row + self.dst.x as usize * c;
+            pixels[start..start + line.len()].copy_from_slice(line);
+        }
+        RgbBuf {
+            pixels,
+            width,
+            height,
+            format: buf.format,
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::alpha::{apply_alpha, AlphaMode};
+use crate::auto;
+use crate::backend::CaptureBackend;
+use crate::buf::{check_slice, PixelFormat, RgbBuf};
+use crate::capture::{Area, Output, Using};
+use crate::error::Error;
+#[cfg(windows)]
+use crate::gdi::{set_dpi_awareness, BitBltBackend, GdiBackend, PrintWindowBackend};
+use crate::pool::BufPool;
+use crate::region::{Fit, Region, RegionPolicy};
+#[cfg(all(unix, feature = "x11"))]
+use crate::x11::X11Backend;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Target {
+    // entire virtual screen, all monitors
+    Display,
+    Window(isize),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dpi {
+    // SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE) before capture
+    PerMonitorAware,
+    // leave process DPI awareness as it is
+    Keep,
+}
+
+#[derive(Clone, Copy)]
+pub struct CaptureRequest {
+    pub(crate) target: Target,
+    pub(crate) using: Using,
+    pub(crate) area: Area,
+    pub(crate) crop_xy: Option<[i32; 2]>,
+    pub(crate) crop_wh: Option<[i32; 2]>,
+    pub(crate) policy: RegionPolicy,
+    pub(crate) output: Output,
+    pub(crate) dpi: Dpi,
+}
+
+impl CaptureRequest {
+    pub fn new(target: Target) -> CaptureRequest {
+        CaptureRequest {
+            target,
+            using: Using::PrintWindow,
+            area: Area::Full,
+            crop_xy: None,
+            crop_wh: None,
+            policy: RegionPolicy::Error,
+            output: Output::default(),
+            dpi: Dpi::PerMonitorAware,
+        }
+    }
+
+    pub fn window(hwnd: isize) -> CaptureRequest {
+        CaptureRequest::new(Target::Window(hwnd))
+    }
+
+    pub fn display() -> CaptureRequest {
+        CaptureRequest::new(Target::Display)
+    }
+
+    pub fn using(mut self, using: Using) -> CaptureRequest {
+        self.using = using;
+        self
+    }
+
+    pub fn area(mut self, area: Area) -> CaptureRequest {
+        self.area = area;
+        self
+    }
+
+    pub fn region(mut self, region: Region) -> CaptureRequest {
+        self.crop_xy = Some([region.x, region.y]);
+        self.crop_wh = Some([region.width, region.height]);
+        self
+    }
+
+    pub fn region_policy(mut self, policy: RegionPolicy) -> CaptureRequest {
+        self.policy = policy;
+        self
+    }
+
+    pub(crate) fn crop(
+        mut self,
+        crop_xy: Option<[i32; 2]>,
+        crop_wh: Option<[i32; 2]>,
+    ) -> CaptureRequest {
+        self.crop_xy = crop_xy;
+        self.crop_wh = crop_wh;
+        self
+    }
+
+    pub fn output(mut self, output: Output) -> CaptureRequest {
+        self.output = output;
+        self
+    }
+
+    pub fn format(mut self, format: PixelFormat) -> CaptureRequest {
+        self.output.format = format;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: AlphaMode) -> CaptureRequest {
+        self.output.alpha = alpha;
+        self
+    }
+
+    pub fn unmirror(mut self, unmirror: bool) -> CaptureRequest {
+        self.output.unmirror = unmirror;
+        self
+    }
+
+    pub fn dpi(mut self, dpi: Dpi) -> CaptureRequest {
+        self.dpi = dpi;
+        self
+    }
+
+    #[cfg(windows)]
+    pub fn capture(&self) -> Result<RgbBuf, Error> {
+        set_dpi_awareness(self.dpi);
+        match self.using {
+            Using::BitBlt => self.capture_with(&mut BitBltBackend),
+            Using::PrintWindow => self.capture_with(&mut PrintWindowBackend),
+            Using::Auto => self.one_off(|backend| self.capture_with(backend)),
+            Using::Composite => Err(Error::Unsupported),
+        }
+    }
+
+    #[cfg(not(windows))]
+    pub fn capture(&self) -> Result<RgbBuf, Error> {
+        self.one_off(|backend| self.capture_with(backend))
+    }
+
+    // capture and the method that made it, Using::Auto resolves to BitBlt or PrintWindow
+    pub fn capture_method(&self) -> Result<(RgbBuf, Using), Error> {
+        self.one_off(|backend| self.capture_method_with(backend))
+    }
+
+    // capture reusing the allocation of out
+    pub fn capture_into(&self, out: &mut RgbBuf) -> Result<(), Error> {
+        self.one_off(|backend| self.capture_into_with(backend, out))
+    }
+
+    // capture straight into caller memory, see capture_to_slice_with
+    pub fn capture_to_slice(&self, dst: &mut [u8], stride: usize) -> Result<(u32, u32), Error> {
+        self.one_off(|backend| self.capture_to_slice_with(backend, dst, stride))
+    }
+
+    // f on a platform backend set up for this call; Using::Auto starts from and
+    // adds to what earlier one-off captures of the target found, see auto_method
+    fn one_off<T>(
+        &self,
+        f: impl FnOnce(&mut dyn CaptureBackend) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut backend = self.backend()?;
+        if let Some(auto) = backend.auto_methods() {
+            auto::load_shared(auto, self.target);
+        }
+        let result = f(&mut *backend);
+        if let Some(auto) = backend.auto_methods() {
+            auto::store_shared(auto, self.target);
+        }
+        result
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn backend(&self) -> Result<Box<dyn CaptureBackend>, Error> {
+        set_dpi_awareness(self.dpi);
+        Ok(Box::new(GdiBackend::new(self.using)))
+    }
+
+    #[cfg(all(unix, feature = "x11"))]
+    pub(crate) fn backend(&self) -> Result<Box<dyn CaptureBackend>, Error> {
+        let mut backend = X11Backend::connect()?;
+        backend.set_using(self.using);
+        Ok(Box::new(backend))
+    }
+
+    #[cfg(not(any(windows, all(unix, feature = "x11"))))]
+    pub(crate) fn backend(&self) -> Result<Box<dyn CaptureBackend>, Error> {
+        Err(Error::Unsupported)
+    }
+
+    // using and dpi are up to the backend
+    pub fn capture_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+    ) -> Result<RgbBuf, Error> {
+        let mut buf = RgbBuf::empty();
+        self.capture_into_with(backend, &mut buf)?;
+        Ok(buf)
+    }
+
+    // like capture_with, reusing the allocation of out where the pipeline allows
+    pub fn capture_into_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        out: &mut RgbBuf,
+    ) -> Result<(), Error> {
+        self.capture_method_into_with(backend, out).map(|_| ())
+    }
+
+    pub fn capture_method_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+    ) -> Result<(RgbBuf, Using), Error> {
+        let mut buf = RgbBuf::empty();
+        let using = self.capture_method_into_with(backend, &mut buf)?;
+        Ok((buf, using))
+    }
+
+    // Using::Auto switches backend to the method it remembers for the target, the
+    // cheap one for a new target, and falls back when that fails or comes out blank
+    pub fn capture_method_into_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        out: &mut RgbBuf,
+    ) -> Result<Using, Error> {
+        if self.using != Using::Auto {
+            self.capture_grabbed_into(backend, out)?;
+            return Ok(self.using);
+        }
+        let (first, fallback) = match backend.auto_methods() {
+            Some(auto) => auto.methods(self.target, self.area),
+            None => return Err(Error::Unsupported),
+        };
+        backend.set_using(first);
+        let blank = self.capture_grabbed_into(backend, out);
+        let using = match (fallback, blank) {
+            (Some(fallback), Ok(true) | Err(_)) => {
+                backend.set_using(fallback);
+                self.capture_grabbed_into(backend, out)?;
+                fallback
+            }
+            (_, blank) => {
+                blank?;
+                first
+            }
+        };
+        // with no fallback there was no choice to remember
+        if let (Some(auto), Some(_)) = (backend.auto_methods(), fallback) {
+            auto.remember(self.target, using);
+        }
+        Ok(using)
+    }
+
+    // returns whether the grabbed pixels were blank
+    fn capture_grabbed_into<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        out: &mut RgbBuf,
+    ) -> Result<bool, Error> {
+        let (fit, src, mirrored) = self.plan(backend)?;
+        let mut buf = std::mem::replace(out, RgbBuf::empty());
+        let grabbed = match src.width == 0 || src.height == 0 {
+            true => {
+                buf.pixels.clear();
+                (buf.width, buf.height, buf.format) = (0, 0, PixelFormat::Bgra8);
+                Ok(())
+            }
+            false => backend.grab_into(self.target, self.area, src, &mut buf),
+        }
+        .and_then(|_| Ok(buf.check()?));
+        // out keeps its allocation for the next attempt
+        if let Err(e) = grabbed {
+            *out = buf;
+            return Err(e);
+        }
+        let blank = buf.is_blank();
+        *out = self.finish(buf, &fit, mirrored);
+        Ok(blank)
+    }
+
+    // frame in a buffer from pool, put it back when done with it
+    pub fn capture_pooled_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        pool: &BufPool,
+    ) -> Result<RgbBuf, Error> {
+        let mut buf = pool.take();
+        match self.capture_into_with(backend, &mut buf) {
+            Ok(()) => Ok(buf),
+            Err(e) => {
+                pool.put(buf);
+                Err(e)
+            }
+        }
+    }
+
+    // rows of the output format into caller memory, stride bytes apart;
+    // returns width and height of the frame
+    pub fn capture_to_slice_with<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        dst: &mut [u8],
+        stride: usize,
+    ) -> Result<(u32, u32), Error> {
+        self.capture_to_slice_scratch(backend, dst, stride, &mut RgbBuf::empty())
+    }
+
+    // scratch holds frames of formats the backend can't write directly
+    pub(crate) fn capture_to_slice_scratch<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+        dst: &mut [u8],
+        stride: usize,
+        scratch: &mut RgbBuf,
+    ) -> Result<(u32, u32), Error> {
+        let format = self.output.format;
+        // Auto may need a second grab after looking at the first
+        if self.using == Using::Auto || !matches!(format, PixelFormat::Bgra8 | PixelFormat::Rgba8) {
+            self.capture_into_with(backend, scratch)?;
+            scratch.copy_to_slice(dst, stride)?;
+            return Ok((scratch.width, scratch.height));
+        }
+
+        let (fit, src, mirrored) = self.plan(backend)?;
+        let (width, height) = (fit.width as u32, fit.height as u32);
+        let row = width as usize * 4;
+        check_slice(dst.len(), stride, row, height)?;
+        if let RegionPolicy::PadWithColor(color) = self.policy {
+            if fit.dst != Region::new(0, 0, fit.width, fit.height) {
+                for y in 0..height as usize {
+                    dst[y * stride..y * stride + row]
+                        .chunks_exact_mut(4)
+                        .for_each(|px| format.write(color, px));
+                }
+            }
+        }
+        if src.width == 0 || src.height == 0 {
+            return Ok((width, height));
+        }
+
+        let offset = fit.dst.y as usize * stride + fit.dst.x as usize * 4;
+        backend.grab_to_slice(self.target, self.area, src, &mut dst[offset..], stride)?;
+        // finish in place, row by row
+        let len = src.width as usize * 4;
+        for y in 0..src.height as usize {
+            let line = &mut dst[offset + y * stride..offset + y * stride + len];
+            if mirrored {
+                let n = src.width as usize;
+                for x in 0..n / 2 {
+                    let (left, right) = line.split_at_mut((n - 1 - x) * 4);
+                    left[x * 4..x * 4 + 4].swap_with_slice(&mut right[..4]);
+                }
+            }
+            apply_alpha(line, 3, self.output.alpha);
+            if format == PixelFormat::Rgba8 {
+                line.chunks_exact_mut(4).for_each(|c| c.swap(0, 2));
+            }
+        }
+        Ok((width, height))
+    }
+
+    // fitted output, source region to grab and whether it comes mirrored
+    fn plan<B: CaptureBackend + ?Sized>(
+        &self,
+        backend: &mut B,
+    ) -> Result<(Fit, Region, bool), Error> {
+        let geo = backend.geometry(self.target, self.area)?;
+        let fit = Region::from_crop(self.crop_xy, self.crop_wh, geo.width, geo.height).fit(
+            geo.width,
+            geo.height,
+            self.policy,
+        )?;
+
+        let mirrored = self.output.unmirror && geo.mirrored;
+        // region is given in unmirrored coordinates
+        let mut src = fit.src;
+        if mirrored {
+            src.x = geo.width - src.x - src.width;
+        }
+        Ok((fit, src, mirrored))
+    }
+
+    // shared tail of every capture: unmirror, alpha, padding, output format
+    fn finish(&self, buf: RgbBuf, fit: &Fit, mirrored: bool) -> RgbBuf {
+        let buf = match mirrored {
+            true => buf.flip_horizontal(),
+            false => buf,
+        };
+        let buf = buf.apply_alpha(self.output.alpha);
+        let buf = match self.policy {
+            RegionPolicy::PadWithColor(color) => fit.place(buf, color),
+            RegionPolicy::Clamp | RegionPolicy::Error => buf,
+        };
+        buf.into_format(self.output.format)
+    }
+}
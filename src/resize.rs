@@ -0,0 +1,91 @@
+use crate::buf::{BufError, RgbBuf, RgbBufView};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // box filter, averages every source pixel covered by the destination one
+    Area,
+}
+
+// (source index, weight) pairs contributing to each destination index
+fn taps(src: u32, dst: u32, filter: Filter) -> Vec<Vec<(usize, f32)>> {
+    let scale = src as f32 / dst as f32;
+    let last = src as usize - 1;
+    (0..dst)
+        .map(|d| match filter {
+            Filter::Nearest => {
+                let s = ((d as f32 + 0.5) * scale) as usize;
+                vec![(s.min(last), 1.0)]
+            }
+            Filter::Bilinear => {
+                let f = ((d as f32 + 0.5) * scale - 0.5).clamp(0.0, last as f32);
+                let s = f as usize;
+                let t = f - s as f32;
+                vec![(s, 1.0 - t), ((s + 1).min(last), t)]
+            }
+            Filter::Area => {
+                let (start, end) = (d as f32 * scale, (d + 1) as f32 * scale);
+                (start as usize..(end.ceil() as usize).min(src as usize))
+                    .map(|s| {
+                        let cover = end.min(s as f32 + 1.0) - start.max(s as f32);
+                        (s, cover / scale)
+                    })
+                    .filter(|&(_, w)| w > 0.0)
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+impl RgbBufView<'_> {
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> Result<RgbBuf, BufError> {
+        self.check()?;
+        let c = self.format.bytes_per_pixel();
+        let mut pixels = vec![0; RgbBuf::len_for(width, height, self.format)];
+        if self.width == 0 || self.height == 0 || pixels.is_empty() {
+            return Ok(RgbBuf {
+                pixels,
+                width,
+                height,
+                format: self.format,
+            });
+        }
+
+        let xtaps = taps(self.width, width, filter);
+        let ytaps = taps(self.height, height, filter);
+        let row = width as usize * c;
+
+        // horizontal pass over every source row, then vertical pass
+        let mut tmp = vec![0f32; self.height as usize * row];
+        for (src, dst) in self.rows().zip(tmp.chunks_exact_mut(row)) {
+            for (px, taps) in dst.chunks_exact_mut(c).zip(&xtaps) {
+                for &(s, w) in taps {
+                    for (v, &b) in px.iter_mut().zip(&src[s * c..s * c + c]) {
+                        *v += b as f32 * w;
+                    }
+                }
+            }
+        }
+        for (dst, taps) in pixels.chunks_exact_mut(row).zip(&ytaps) {
+            for (i, v) in dst.iter_mut().enumerate() {
+                let sum: f32 = taps.iter().map(|&(s, w)| tmp[s * row + i] * w).sum();
+                *v = (sum + 0.5).clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        Ok(RgbBuf {
+            pixels,
+            width,
+            height,
+            format: self.format,
+        })
+    }
+}
+
+impl RgbBuf {
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> Result<RgbBuf, BufError> {
+        self.check()?;
+        self.as_view().resize(width, height, filter)
+    }
+}
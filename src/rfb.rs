@@ -0,0 +1,475 @@
+// RFB 3.8 (VNC) wire format pieces
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::TcpStream;
+use std::ops::Range;
+
+use des::cipher::generic_array::GenericArray;
+use des::cipher::{BlockEncrypt, KeyInit};
+use des::Des;
+use flate2::{Compress, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::buf::RgbBuf;
+use crate::region::Region;
+
+pub(crate) const VERSION: &[u8; 12] = b"RFB 003.008\n";
+
+// 32 bpp, depth 24, little endian true color: B, G, R, X in memory like Bgra8
+pub(crate) const PIXEL_FORMAT: [u8; 16] = [32, 24, 0, 1, 0, 255, 0, 255, 0, 255, 16, 8, 0, 0, 0, 0];
+
+pub(crate) const SECURITY_NONE: u8 = 1;
+pub(crate) const SECURITY_VNC: u8 = 2;
+
+pub(crate) const RAW: i32 = 0;
+pub(crate) const COPY_RECT: i32 = 1;
+pub(crate) const ZRLE: i32 = 16;
+pub(crate) const DESKTOP_SIZE: i32 = -223;
+
+const TILE: i32 = 64;
+
+// ServerInit and DesktopSize beyond this are refused instead of allocated
+const MAX_PIXELS: usize = 1 << 26;
+
+// failure reasons and desktop names are cut here, the rest is skipped
+const MAX_STRING: u32 = 4096;
+
+pub(crate) fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// true if a message has started arriving or the peer hung up, never blocks
+pub(crate) fn pending(reader: &mut BufReader<TcpStream>) -> io::Result<bool> {
+    if !reader.buffer().is_empty() {
+        return Ok(true);
+    }
+    reader.get_ref().set_nonblocking(true)?;
+    let peeked = reader.fill_buf().map(|b| b.len());
+    reader.get_ref().set_nonblocking(false)?;
+    match peeked {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut b = [0; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+pub(crate) fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut b = [0; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
+}
+
+pub(crate) fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+pub(crate) fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut b = vec![0; len];
+    r.read_exact(&mut b)?;
+    Ok(b)
+}
+
+// reads and drops len bytes without holding them all, for payloads nobody uses
+pub(crate) fn skip<R: Read>(r: &mut R, len: u64) -> io::Result<()> {
+    match io::copy(&mut r.take(len), &mut io::sink())? {
+        n if n == len => Ok(()),
+        _ => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+// u32 length prefixed string, used for failure reasons and the desktop name
+pub(crate) fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_u32(r)?;
+    let s = read_bytes(r, len.min(MAX_STRING) as usize)?;
+    skip(r, len.saturating_sub(MAX_STRING) as u64)?;
+    Ok(String::from_utf8_lossy(&s).into_owned())
+}
+
+pub(crate) fn check_size(width: u16, height: u16) -> io::Result<()> {
+    match width as usize * height as usize {
+        ..=MAX_PIXELS => Ok(()),
+        _ => Err(protocol_error("framebuffer too large")),
+    }
+}
+
+// DES of the challenge keyed by the first 8 password bytes, each bit reversed
+pub(crate) fn vnc_auth(password: &str, challenge: &[u8; 16]) -> [u8; 16] {
+    let mut key = [0u8; 8];
+    for (k, p) in key.iter_mut().zip(password.bytes()) {
+        *k = p.reverse_bits();
+    }
+    let des = Des::new_from_slice(&key).unwrap();
+    let mut out = *challenge;
+    for block in out.chunks_exact_mut(8) {
+        des.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+    out
+}
+
+// 3 byte compressed pixel of PIXEL_FORMAT
+fn cpixel<R: Read>(r: &mut R) -> io::Result<[u8; 4]> {
+    let mut b = [0; 3];
+    r.read_exact(&mut b)?;
+    Ok([b[0], b[1], b[2], 255])
+}
+
+// run length is 1 + sum of bytes up to and including the first one below 255,
+// left is what the tile still has room for
+fn run_length<R: Read>(r: &mut R, left: usize) -> io::Result<usize> {
+    let mut len = 1;
+    loop {
+        let b = read_u8(r)?;
+        len += b as usize;
+        if len > left {
+            return Err(protocol_error("ZRLE run overflows the tile"));
+        }
+        if b != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+// most a width x height ZRLE rectangle inflates to: 1 byte run lengths after every
+// cpixel, plus the subencoding and a full palette per tile
+pub(crate) fn zrle_max_len(width: usize, height: usize) -> usize {
+    let tiles = width.div_ceil(TILE as usize) * height.div_ceil(TILE as usize);
+    width * height * 4 + tiles * (1 + 127 * 3)
+}
+
+// zlib data of one ZRLE rectangle, z carries over between rectangles
+pub(crate) fn inflate(z: &mut Decompress, data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    // one byte of room past limit tells an exact fit from too much data
+    let mut out = Vec::with_capacity((data.len() * 4).min(limit + 1));
+    let start = z.total_in();
+    loop {
+        if out.len() > limit {
+            return Err(protocol_error("ZRLE data inflates past its rectangle"));
+        }
+        if out.len() == out.capacity() {
+            out.reserve_exact(out.capacity().max(4096).min(limit + 1 - out.len()));
+        }
+        let used = (z.total_in() - start) as usize;
+        let status = z
+            .decompress_vec(&data[used..], &mut out, FlushDecompress::Sync)
+            .map_err(|e| protocol_error(&e.to_string()))?;
+        let done = z.total_in() - start == data.len() as u64;
+        match status {
+            Status::Ok => (),
+            // no progress possible, fine once all input is in
+            Status::BufError if done => return Ok(out),
+            Status::BufError => return Err(protocol_error("truncated ZRLE data")),
+            Status::StreamEnd => return Ok(out),
+        }
+    }
+}
+
+// paint one inflated ZRLE rectangle onto a Bgra8 framebuffer
+pub(crate) fn zrle_decode(mut data: &[u8], fb: &mut RgbBuf, rect: Region) -> io::Result<()> {
+    let r = &mut data;
+    for ty in (0..rect.height).step_by(TILE as usize) {
+        for tx in (0..rect.width).step_by(TILE as usize) {
+            let tile = Region::new(
+                rect.x + tx,
+                rect.y + ty,
+                TILE.min(rect.width - tx),
+                TILE.min(rect.height - ty),
+            );
+            let n = (tile.width * tile.height) as usize;
+            let mut pixels = Vec::with_capacity(n);
+            match read_u8(r)? {
+                0 => {
+                    for _ in 0..n {
+                        pixels.push(cpixel(r)?);
+                    }
+                }
+                1 => pixels.resize(n, cpixel(r)?),
+                p @ 2..=16 => {
+                    let palette = (0..p).map(|_| cpixel(r)).collect::<io::Result<Vec<_>>>()?;
+                    let bits = match p {
+                        2 => 1,
+                        3 | 4 => 2,
+                        _ => 4,
+                    };
+                    let row = (tile.width as usize * bits).div_ceil(8);
+                    for _ in 0..tile.height {
+                        let line = read_bytes(r, row)?;
+                        for x in 0..tile.width as usize {
+                            let bit = x * bits;
+                            let i = (line[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1);
+                            pixels.push(*palette.get(i as usize).ok_or_else(bad_index)?);
+                        }
+                    }
+                }
+                128 => {
+                    while pixels.len() < n {
+                        let px = cpixel(r)?;
+                        let len = run_length(r, n - pixels.len())?;
+                        pixels.resize(pixels.len() + len, px);
+                    }
+                }
+                p @ 130..=255 => {
+                    let palette = (0..p - 128)
+                        .map(|_| cpixel(r))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    while pixels.len() < n {
+                        let i = read_u8(r)?;
+                        let px = *palette.get((i & 127) as usize).ok_or_else(bad_index)?;
+                        let len = match i & 128 {
+                            0 => 1,
+                            _ => run_length(r, n - pixels.len())?,
+                        };
+                        pixels.resize(pixels.len() + len, px);
+                    }
+                }
+                _ => return Err(protocol_error("unknown ZRLE subencoding")),
+            }
+            paint(fb, tile, &pixels);
+        }
+    }
+    Ok(())
+}
+
+fn bad_index() -> io::Error {
+    protocol_error("ZRLE palette index out of range")
+}
+
+// region must lie inside buf, Bgra8 pixels row by row
+pub(crate) fn pixels(buf: &RgbBuf, region: Region) -> Option<Vec<[u8; 4]>> {
+    Some(
+        buf.view(region)
+            .ok()?
+            .rows()
+            .flat_map(|row| row.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]))
+            .collect(),
+    )
+}
+
+// rect must lie inside fb, pixels row by row
+pub(crate) fn paint(fb: &mut RgbBuf, rect: Region, pixels: &[[u8; 4]]) {
+    let width = fb.width as usize;
+    for (y, row) in pixels.chunks_exact(rect.width as usize).enumerate() {
+        let start = ((rect.y as usize + y) * width + rect.x as usize) * 4;
+        for (px, dst) in row.iter().zip(fb.pixels[start..].chunks_exact_mut(4)) {
+            dst.copy_from_slice(px);
+        }
+    }
+}
+
+// true color pixel format a viewer asked for with SetPixelFormat
+#[derive(Debug, Clone)]
+pub(crate) struct ClientFormat {
+    bytes: usize,
+    big_endian: bool,
+    max: [u32; 3],
+    shift: [u32; 3],
+    // bytes of a pixel that make up a ZRLE cpixel
+    cpixel: Range<usize>,
+}
+
+impl ClientFormat {
+    pub(crate) fn parse(raw: &[u8; 16]) -> io::Result<ClientFormat> {
+        let bytes = match raw[0] {
+            8 | 16 | 32 => raw[0] as usize / 8,
+            _ => return Err(protocol_error("unsupported bits per pixel")),
+        };
+        if raw[3] == 0 {
+            return Err(protocol_error("colour map pixel formats are not supported"));
+        }
+        let max = [0, 2, 4].map(|i| u16::from_be_bytes([raw[4 + i], raw[5 + i]]) as u32);
+        let shift = [raw[10] as u32, raw[11] as u32, raw[12] as u32];
+        let big_endian = raw[2] != 0;
+        let mask = (0..3).fold(0u64, |m, i| m | (max[i] as u64) << shift[i]);
+        // 32 bpp with all color bits in 3 of the bytes drops the 4th
+        let cpixel = match (bytes, raw[1] <= 24, big_endian) {
+            (4, true, false) if mask < 1 << 24 => 0..3,
+            (4, true, true) if mask < 1 << 24 => 1..4,
+            (4, true, false) if mask & 0xff == 0 => 1..4,
+            (4, true, true) if mask & 0xff == 0 => 0..3,
+            _ => 0..bytes,
+        };
+        Ok(ClientFormat {
+            bytes,
+            big_endian,
+            max,
+            shift,
+            cpixel,
+        })
+    }
+
+    // one Bgra8 pixel in this format
+    pub(crate) fn encode(&self, px: &[u8]) -> ([u8; 4], usize) {
+        let value = [px[2], px[1], px[0]]
+            .iter()
+            .enumerate()
+            .fold(0u32, |v, (i, &c)| {
+                v | ((c as u32 * self.max[i] + 127) / 255) << self.shift[i]
+            });
+        let mut out = [0; 4];
+        match self.big_endian {
+            true => out[..self.bytes].copy_from_slice(&value.to_be_bytes()[4 - self.bytes..]),
+            false => out[..self.bytes].copy_from_slice(&value.to_le_bytes()[..self.bytes]),
+        }
+        (out, self.bytes)
+    }
+
+    pub(crate) fn put(&self, px: &[u8], out: &mut Vec<u8>) {
+        let (b, len) = self.encode(px);
+        out.extend_from_slice(&b[..len]);
+    }
+
+    fn put_cpixel(&self, px: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.encode(px).0[self.cpixel.clone()]);
+    }
+}
+
+fn run_bytes(len: usize) -> usize {
+    (len - 1) / 255 + 1
+}
+
+fn put_run(len: usize, out: &mut Vec<u8>) {
+    let mut rest = len - 1;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+// ZRLE rectangle of a Bgra8 frame, length prefixed and deflated with the connection stream
+pub(crate) fn zrle_encode(
+    frame: &RgbBuf,
+    rect: Region,
+    format: &ClientFormat,
+    deflate: &mut Compress,
+) -> io::Result<Vec<u8>> {
+    let mut tiles = Vec::new();
+    for ty in (0..rect.height).step_by(TILE as usize) {
+        for tx in (0..rect.width).step_by(TILE as usize) {
+            let tile = Region::new(
+                rect.x + tx,
+                rect.y + ty,
+                TILE.min(rect.width - tx),
+                TILE.min(rect.height - ty),
+            );
+            let pixels = pixels(frame, tile).ok_or_else(|| protocol_error("tile out of bounds"))?;
+            zrle_tile(&pixels, tile.width as usize, format, &mut tiles);
+        }
+    }
+
+    let mut data = Vec::with_capacity(tiles.len() / 2 + 64);
+    let start = deflate.total_in();
+    loop {
+        let used = (deflate.total_in() - start) as usize;
+        if used == tiles.len() && data.len() < data.capacity() {
+            break;
+        }
+        if data.len() == data.capacity() {
+            data.reserve(data.capacity().max(4096));
+        }
+        deflate
+            .compress_vec(&tiles[used..], &mut data, FlushCompress::Sync)
+            .map_err(io::Error::other)?;
+    }
+    let mut out = (data.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+// picks the smallest of solid, raw, packed palette, plain RLE and palette RLE
+fn zrle_tile(pixels: &[[u8; 4]], width: usize, format: &ClientFormat, out: &mut Vec<u8>) {
+    let key = |p: &[u8; 4]| u32::from_le_bytes([p[0], p[1], p[2], 0]);
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut index = HashMap::new();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, p) in pixels.iter().enumerate() {
+        if index.len() <= 127 && !index.contains_key(&key(p)) {
+            index.insert(key(p), palette.len());
+            palette.push(*p);
+        }
+        match runs.last_mut() {
+            Some((start, len)) if key(&pixels[*start]) == key(p) => *len += 1,
+            _ => runs.push((i, 1)),
+        }
+    }
+    let colors = palette.len();
+    let cp = format.cpixel.len();
+    if colors == 1 {
+        out.push(1);
+        format.put_cpixel(&palette[0], out);
+        return;
+    }
+
+    let height = pixels.len() / width;
+    let bits = match colors {
+        2 => 1,
+        3 | 4 => 2,
+        _ => 4,
+    };
+    let row = (width * bits).div_ceil(8);
+    let raw = pixels.len() * cp;
+    let plain_rle: usize = runs.iter().map(|&(_, l)| cp + run_bytes(l)).sum();
+    let packed = match colors {
+        ..=16 => colors * cp + height * row,
+        _ => usize::MAX,
+    };
+    let palette_rle = match colors {
+        ..=127 => {
+            colors * cp
+                + runs
+                    .iter()
+                    .map(|&(_, l)| if l == 1 { 1 } else { 1 + run_bytes(l) })
+                    .sum::<usize>()
+        }
+        _ => usize::MAX,
+    };
+    let best = raw.min(plain_rle).min(packed).min(palette_rle);
+
+    if best == raw {
+        out.push(0);
+        for p in pixels {
+            format.put_cpixel(p, out);
+        }
+    } else if best == packed {
+        out.push(colors as u8);
+        for p in &palette {
+            format.put_cpixel(p, out);
+        }
+        for line in pixels.chunks_exact(width) {
+            let mut bytes = vec![0u8; row];
+            for (x, p) in line.iter().enumerate() {
+                let bit = x * bits;
+                bytes[bit / 8] |= (index[&key(p)] as u8) << (8 - bits - bit % 8);
+            }
+            out.extend_from_slice(&bytes);
+        }
+    } else if best == palette_rle {
+        out.push(128 + colors as u8);
+        for p in &palette {
+            format.put_cpixel(p, out);
+        }
+        for &(start, len) in &runs {
+            let i = index[&key(&pixels[start])] as u8;
+            match len {
+                1 => out.push(i),
+                _ => {
+                    out.push(i | 128);
+                    put_run(len, out);
+                }
+            }
+        }
+    } else {
+        out.push(128);
+        for &(start, len) in &runs {
+            format.put_cpixel(&pixels[start], out);
+            put_run(len, out);
+        }
+    }
+}
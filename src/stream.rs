@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::CaptureBackend;
+use crate::buf::RgbBuf;
+use crate::capturer::WindowCapturer;
+use crate::error::Error;
+#[cfg(windows)]
+use crate::gdi::GdiBackend;
+use crate::pool::BufPool;
+use crate::request::CaptureRequest;
+
+// time since some fixed start, and a way to wait for it
+pub trait Clock {
+    fn now(&self) -> Duration;
+    fn sleep_until(&mut self, deadline: Duration);
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&mut self, deadline: Duration) {
+        thread::sleep(deadline.saturating_sub(self.now()));
+    }
+}
+
+// manual time for tests: sleeping jumps to the deadline, clones share the time
+#[derive(Clone, Default)]
+pub struct FakeClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl FakeClock {
+    pub fn new() -> FakeClock {
+        FakeClock::default()
+    }
+
+    // time passing outside of sleeps, e.g. inside a grab
+    pub fn advance(&self, by: Duration) {
+        *self.time() += by;
+    }
+
+    fn time(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        *self.time()
+    }
+
+    fn sleep_until(&mut self, deadline: Duration) {
+        let mut now = self.time();
+        *now = (*now).max(deadline);
+    }
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub buf: RgbBuf,
+    // frames delivered before this one
+    pub seq: u64,
+    // since the first frame of the stream
+    pub timestamp: Duration,
+    // frame slots skipped right before this frame because capture fell behind
+    pub dropped: u64,
+}
+
+// endless frames of one request at a target rate; frame n is due at
+// start + n / fps, missed slots are dropped rather than caught up on
+pub struct CaptureStream<B, C = SystemClock> {
+    capturer: WindowCapturer<B>,
+    clock: C,
+    interval: Duration,
+    pool: Option<BufPool>,
+    start: Option<Duration>,
+    slot: u64,
+    seq: u64,
+    dropped: u64,
+}
+
+#[cfg(windows)]
+impl CaptureStream<GdiBackend> {
+    pub fn new(request: CaptureRequest, fps: f64) -> CaptureStream<GdiBackend> {
+        CaptureStream::from_capturer(WindowCapturer::new(request), fps)
+    }
+}
+
+impl<B: CaptureBackend> CaptureStream<B> {
+    pub fn with_backend(backend: B, request: CaptureRequest, fps: f64) -> CaptureStream<B> {
+        CaptureStream::from_capturer(WindowCapturer::with_backend(backend, request), fps)
+    }
+
+    // panics unless fps is positive and finite
+    pub fn from_capturer(capturer: WindowCapturer<B>, fps: f64) -> CaptureStream<B> {
+        assert!(fps > 0.0 && fps.is_finite(), "fps must be positive");
+        CaptureStream {
+            capturer,
+            clock: SystemClock::default(),
+            interval: Duration::from_secs_f64(1.0 / fps),
+            pool: None,
+            start: None,
+            slot: 0,
+            seq: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl<B: CaptureBackend, C: Clock> CaptureStream<B, C> {
+    pub fn clock<C2: Clock>(self, clock: C2) -> CaptureStream<B, C2> {
+        CaptureStream {
+            capturer: self.capturer,
+            clock,
+            interval: self.interval,
+            pool: self.pool,
+            start: self.start,
+            slot: self.slot,
+            seq: self.seq,
+            dropped: self.dropped,
+        }
+    }
+
+    // frames come in buffers from pool, put them back when done
+    pub fn pool(mut self, pool: BufPool) -> CaptureStream<B, C> {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // dropped slots over the whole stream
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn capturer(&mut self) -> &mut WindowCapturer<B> {
+        &mut self.capturer
+    }
+
+    fn due(&self, start: Duration, slot: u64) -> Duration {
+        start + Duration::from_nanos((self.interval.as_nanos() * slot as u128) as u64)
+    }
+}
+
+impl<B: CaptureBackend, C: Clock> Iterator for CaptureStream<B, C> {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = self.clock.now();
+        let start = *self.start.get_or_insert(now);
+        let due = self.due(start, self.slot);
+        let mut dropped = 0;
+        if now < due {
+            self.clock.sleep_until(due);
+        } else {
+            // a whole slot or more behind: skip to the current one
+            let current = ((now - start).as_nanos() / self.interval.as_nanos()) as u64;
+            if current > self.slot {
+                dropped = current - self.slot;
+                self.slot = current;
+            }
+        }
+        let timestamp = self.clock.now() - start;
+        self.slot += 1;
+        self.dropped += dropped;
+
+        let buf = match &self.pool {
+            Some(pool) => self.capturer.capture_pooled(pool),
+            None => {
+                let mut buf = RgbBuf::empty();
+                self.capturer.capture_into(&mut buf).map(|_| buf)
+            }
+        };
+        Some(buf.map(|buf| {
+            let frame = Frame {
+                buf,
+                seq: self.seq,
+                timestamp,
+                dropped,
+            };
+            self.seq += 1;
+            frame
+        }))
+    }
+}
@@ -1,9 +1,44 @@
 use core::fmt;
 
 use crate::prelude::*;
+#[cfg(windows)]
 use image::{DynamicImage, RgbaImage};
+#[cfg(windows)]
 use regex::Regex;
 
+mod alpha;
+#[cfg(feature = "tokio")]
+mod async_capture;
+mod auto;
+mod backend;
+mod buf;
+mod capturer;
+mod error;
+#[cfg(all(target_os = "linux", feature = "fbdev"))]
+mod fbdev;
+#[cfg(feature = "image")]
+mod img;
+#[cfg(any(
+    all(unix, feature = "x11"),
+    all(target_os = "linux", feature = "fbdev"),
+    all(target_os = "linux", feature = "wayland")
+))]
+mod packed;
+mod pool;
+mod region;
+mod resize;
+mod stream;
+mod transform;
+#[cfg(feature = "vnc")]
+mod vnc;
+#[cfg(feature = "vnc")]
+mod vnc_server;
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+mod wayland;
+#[cfg(all(unix, feature = "x11"))]
+mod x11;
+mod yuv;
+
 impl fmt::Display for Area {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -13,6 +48,7 @@ impl fmt::Display for Area {
     }
 }
 
+#[cfg(windows)]
 fn cutr(hwnd: isize, area: Area, crop_xy: Option<[i32; 2]>, crop_wh: Option<[i32; 2]>) {
     let name = format!("{}-{:?}-{:?}", area, crop_xy, crop_wh);
     let b = capture_window_ex(hwnd, Using::PrintWindow, area, crop_xy, crop_wh).unwrap();
@@ -29,6 +65,7 @@ fn cutr(hwnd: isize, area: Area, crop_xy: Option<[i32; 2]>, crop_wh: Option<[i32
         .unwrap();
 }
 
+#[cfg(windows)]
 #[test]
 fn enumerate_params() {
     let re = Regex::new(r"cmd").unwrap();
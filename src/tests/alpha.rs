@@ -0,0 +1,43 @@
+use crate::prelude::*;
+
+fn bgra() -> RgbBuf {
+    RgbBuf::new(
+        vec![10, 20, 30, 0, 50, 100, 128, 128, 1, 2, 3, 255],
+        3,
+        1,
+        PixelFormat::Bgra8,
+    )
+    .unwrap()
+}
+
+#[test]
+fn alpha_opaque() {
+    let b = bgra().apply_alpha(AlphaMode::Opaque);
+    assert_eq!(
+        b.pixels,
+        vec![10, 20, 30, 255, 50, 100, 128, 255, 1, 2, 3, 255]
+    );
+}
+
+#[test]
+fn alpha_preserve() {
+    assert_eq!(
+        bgra().apply_alpha(AlphaMode::Preserve).pixels,
+        bgra().pixels
+    );
+}
+
+#[test]
+fn alpha_unpremultiply() {
+    let b = bgra().apply_alpha(AlphaMode::PremultipliedToStraight);
+    assert_eq!(
+        b.pixels,
+        vec![10, 20, 30, 0, 100, 199, 255, 128, 1, 2, 3, 255]
+    );
+}
+
+#[test]
+fn alpha_ignored_without_channel() {
+    let b = RgbBuf::new(vec![1, 2, 3], 1, 1, PixelFormat::Rgb8).unwrap();
+    assert_eq!(b.apply_alpha(AlphaMode::Opaque).pixels, vec![1, 2, 3]);
+}
@@ -0,0 +1,176 @@
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use futures_core::Stream;
+
+use crate::prelude::*;
+
+const HWND: isize = 11;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+fn mock() -> MockBackend {
+    let mut m = MockBackend::new();
+    for n in 0..3 {
+        let frame = RgbBuf::new(vec![n; 2 * 2 * 4], 2, 2, PixelFormat::Bgra8).unwrap();
+        m.push_frame(Target::Window(HWND), frame);
+    }
+    m
+}
+
+// every grab waits for a token from the test
+struct Gated {
+    mock: MockBackend,
+    gate: mpsc::Receiver<()>,
+    grabs: Arc<AtomicUsize>,
+}
+
+impl CaptureBackend for Gated {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.mock.targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        self.mock.geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        self.gate.recv().unwrap();
+        self.grabs.fetch_add(1, Ordering::SeqCst);
+        self.mock.grab(target, area, src)
+    }
+}
+
+async fn next(s: &mut FrameStream) -> Option<Result<Frame, Error>> {
+    poll_fn(|cx| Pin::new(&mut *s).poll_next(cx)).await
+}
+
+#[test]
+fn capture_on_backend_thread() {
+    let c = AsyncCapturer::with_backend(mock(), 2);
+    let req = CaptureRequest::window(HWND).alpha(AlphaMode::Preserve);
+    runtime().block_on(async {
+        for n in 0..3 {
+            assert_eq!(c.capture(req).await.unwrap().pixels[0], n);
+        }
+        assert!(matches!(
+            c.capture(CaptureRequest::display()).await,
+            Err(Error::TargetNotFound(Target::Display))
+        ));
+    });
+}
+
+#[test]
+fn zero_capacity_is_one() {
+    let c = AsyncCapturer::with_backend(mock(), 0);
+    let stream = CaptureStream::with_backend(mock(), CaptureRequest::window(HWND), 200.0);
+    let mut s = FrameStream::from_stream(stream, 0);
+    runtime().block_on(async {
+        c.capture(CaptureRequest::window(HWND)).await.unwrap();
+        assert_eq!(next(&mut s).await.unwrap().unwrap().seq, 0);
+    });
+}
+
+#[test]
+fn dropped_capture_is_skipped() {
+    let (open, gate) = mpsc::channel();
+    let grabs = Arc::new(AtomicUsize::new(0));
+    let gated = Gated {
+        mock: mock(),
+        gate,
+        grabs: grabs.clone(),
+    };
+    let c = AsyncCapturer::with_backend(gated, 4);
+    let req = CaptureRequest::window(HWND);
+    runtime().block_on(async {
+        let first = tokio::spawn({
+            let c = c.clone();
+            async move { c.capture(req).await }
+        });
+        let second = tokio::spawn({
+            let c = c.clone();
+            async move { c.capture(req).await }
+        });
+        // both queued, the thread holds the first in its grab
+        tokio::task::yield_now().await;
+        second.abort();
+        assert!(second.await.unwrap_err().is_cancelled());
+        // enough tokens that a grab for the second can't hang the test
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        first.await.unwrap().unwrap();
+        c.capture(req).await.unwrap();
+    });
+    assert_eq!(grabs.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn stream_frames_in_order() {
+    let stream = CaptureStream::with_backend(mock(), CaptureRequest::window(HWND), 200.0);
+    let mut s = FrameStream::from_stream(stream, 2);
+    runtime().block_on(async {
+        for seq in 0..3 {
+            let frame = next(&mut s).await.unwrap().unwrap();
+            assert_eq!(frame.seq, seq);
+        }
+    });
+}
+
+#[test]
+fn slow_reader_drops_frames() {
+    let stream = CaptureStream::with_backend(mock(), CaptureRequest::window(HWND), 200.0);
+    let mut s = FrameStream::from_stream(stream, 1);
+    runtime().block_on(async {
+        next(&mut s).await.unwrap().unwrap();
+        // the capture thread blocks on the full channel meanwhile
+        thread::sleep(Duration::from_millis(100));
+        let mut dropped = 0;
+        for _ in 0..4 {
+            dropped += next(&mut s).await.unwrap().unwrap().dropped;
+        }
+        assert!(dropped > 0);
+    });
+}
+
+#[test]
+fn cancel_ends_stream() {
+    let stream = CaptureStream::with_backend(mock(), CaptureRequest::window(HWND), 200.0);
+    let mut s = FrameStream::from_stream(stream, 2);
+    runtime().block_on(async {
+        next(&mut s).await.unwrap().unwrap();
+        s.cancel();
+        // at most what was already queued
+        let mut left = 0;
+        while next(&mut s).await.is_some() {
+            left += 1;
+        }
+        assert!(left <= 2);
+    });
+}
+
+#[test]
+fn setup_error_is_the_only_item() {
+    let mut s = FrameStream::spawn::<MockBackend, SystemClock, _>(1, || Err(Error::Unsupported));
+    runtime().block_on(async {
+        assert!(next(&mut s).await.unwrap().is_err());
+        assert!(next(&mut s).await.is_none());
+    });
+}
+
+#[test]
+fn zero_fps_is_an_error() {
+    let mut s = FrameStream::new(CaptureRequest::display(), 0.0, 1);
+    runtime().block_on(async {
+        assert!(matches!(next(&mut s).await, Some(Err(Error::Fps(_)))));
+        assert!(next(&mut s).await.is_none());
+    });
+}
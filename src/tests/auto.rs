@@ -0,0 +1,185 @@
+use crate::auto;
+use crate::prelude::*;
+
+// 3x2 black but for one green pixel, for every window in hwnds
+fn mock(hwnds: &[isize]) -> MockBackend {
+    let mut pixels = vec![0; 3 * 2 * 4];
+    pixels[5] = 200;
+    let frame = RgbBuf::new(pixels, 3, 2, PixelFormat::Bgra8).unwrap();
+    let mut m = MockBackend::new();
+    for &hwnd in hwnds {
+        m.push_frame(Target::Window(hwnd), frame.as_view().to_owned());
+    }
+    m
+}
+
+#[test]
+fn blank_frames() {
+    let buf = |pixels: Vec<u8>, format| RgbBuf::new(pixels, 2, 1, format).unwrap();
+    // alpha is ignored either way
+    assert!(buf(vec![0, 0, 0, 255, 0, 0, 0, 0], PixelFormat::Bgra8).is_blank());
+    assert!(!buf(vec![0, 0, 0, 0, 0, 1, 0, 0], PixelFormat::Rgba8).is_blank());
+    assert!(buf(vec![0; 6], PixelFormat::Rgb8).is_blank());
+    assert!(!buf(vec![0, 0, 0, 0, 0, 9], PixelFormat::Rgb8).is_blank());
+    assert!(!buf(vec![0, 1], PixelFormat::Gray8).is_blank());
+    assert!(!RgbBuf::new(Vec::new(), 0, 0, PixelFormat::Gray8)
+        .unwrap()
+        .is_blank());
+}
+
+#[test]
+fn auto_keeps_working_bitblt() {
+    let mut m = mock(&[201]);
+    let req = CaptureRequest::window(201).using(Using::Auto);
+    for grabs in 1..=2 {
+        let (buf, using) = req.capture_method_with(&mut m).unwrap();
+        assert_eq!(using, Using::BitBlt);
+        assert_eq!(buf.pixels[5], 200);
+        assert_eq!(m.grabs(), grabs);
+    }
+}
+
+#[test]
+fn auto_falls_back_and_remembers() {
+    let target = Target::Window(202);
+    let mut m = mock(&[202, 203]);
+    m.black_with(target, Using::BitBlt);
+    let req = CaptureRequest::window(202).using(Using::Auto);
+
+    let (buf, using) = req.capture_method_with(&mut m).unwrap();
+    assert_eq!(using, Using::PrintWindow);
+    assert_eq!(buf.pixels[5], 200);
+    assert_eq!(m.grabs(), 2);
+    // straight to PrintWindow from now on
+    let (_, using) = req.capture_method_with(&mut m).unwrap();
+    assert_eq!((using, m.grabs()), (Using::PrintWindow, 3));
+    assert_eq!(m.using(), Some(Using::PrintWindow));
+
+    // another window starts with BitBlt
+    let (_, using) = CaptureRequest::window(203)
+        .using(Using::Auto)
+        .capture_method_with(&mut m)
+        .unwrap();
+    assert_eq!(using, Using::BitBlt);
+
+    m.auto_methods().unwrap().forget(target);
+    let (_, using) = req.capture_method_with(&mut m).unwrap();
+    assert_eq!((using, m.grabs()), (Using::PrintWindow, 6));
+}
+
+#[test]
+fn auto_memory_is_per_backend() {
+    let target = Target::Window(206);
+    let mut m = mock(&[206]);
+    m.black_with(target, Using::BitBlt);
+    let req = CaptureRequest::window(206).using(Using::Auto);
+    req.capture_method_with(&mut m).unwrap();
+    assert_eq!(m.grabs(), 2);
+
+    // a fresh backend knows nothing about the window
+    let mut other = mock(&[206]);
+    let (_, using) = req.capture_method_with(&mut other).unwrap();
+    assert_eq!((using, other.grabs()), (Using::BitBlt, 1));
+}
+
+// forwards everything but the Auto memory, like a backend with one method
+struct Single(MockBackend);
+
+impl CaptureBackend for Single {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.0.targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        self.0.geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        self.0.grab(target, area, src)
+    }
+}
+
+#[test]
+fn auto_needs_a_fallback() {
+    let mut single = Single(mock(&[207]));
+    let req = CaptureRequest::window(207);
+    assert!(matches!(
+        req.using(Using::Auto).capture_method_with(&mut single),
+        Err(Error::Unsupported)
+    ));
+    assert_eq!(single.0.grabs(), 0);
+    let (_, using) = req
+        .using(Using::BitBlt)
+        .capture_method_with(&mut single)
+        .unwrap();
+    assert_eq!(using, Using::BitBlt);
+}
+
+#[test]
+fn auto_checks_grabbed_pixels_only() {
+    let target = Target::Window(204);
+    let mut m = mock(&[204]);
+    m.black_with(target, Using::BitBlt);
+    // the padding is colored, the part read from the window is not
+    let req = CaptureRequest::window(204)
+        .using(Using::Auto)
+        .region(Region::new(2, 1, 2, 2))
+        .region_policy(RegionPolicy::PadWithColor([9, 9, 9, 255]));
+    let mut dst = [0; 2 * 2 * 4];
+    assert_eq!(
+        req.capture_to_slice_with(&mut m, &mut dst, 8).unwrap(),
+        (2, 2)
+    );
+    assert_eq!(m.grabs(), 2);
+    assert_eq!(m.using(), Some(Using::PrintWindow));
+}
+
+#[test]
+fn fixed_method_is_reported() {
+    let mut m = mock(&[205]);
+    let req = CaptureRequest::window(205).using(Using::BitBlt);
+    let (_, using) = req.capture_method_with(&mut m).unwrap();
+    assert_eq!(using, Using::BitBlt);
+    // only Auto switches the backend
+    assert_eq!(m.using(), None);
+}
+
+#[test]
+fn auto_full_area_takes_the_one_method() {
+    let mut m = mock(&[206]);
+    m.set_auto(AutoMethods::default().full_area(Using::PrintWindow));
+    let req = CaptureRequest::window(206).using(Using::Auto);
+    let (_, using) = req.capture_method_with(&mut m).unwrap();
+    assert_eq!((using, m.grabs()), (Using::PrintWindow, 1));
+    // nothing learned for the client area
+    let (_, using) = req
+        .area(Area::ClientOnly)
+        .capture_method_with(&mut m)
+        .unwrap();
+    assert_eq!((using, m.grabs()), (Using::BitBlt, 2));
+}
+
+#[test]
+fn one_off_auto_memory() {
+    let target = Target::Window(207);
+    let mut first = AutoMethods::default();
+    first.remember(target, Using::PrintWindow);
+    auto::store_shared(&first, target);
+    assert_eq!(auto_method(target), Some(Using::PrintWindow));
+
+    // a backend set up later starts where the first left off
+    let mut later = AutoMethods::default();
+    auto::load_shared(&mut later, target);
+    assert_eq!(
+        later.methods(target, Area::ClientOnly),
+        (Using::PrintWindow, None)
+    );
+
+    forget_auto(target);
+    assert_eq!(auto_method(target), None);
+    auto::load_shared(&mut later, target);
+    assert_eq!(
+        later.methods(target, Area::ClientOnly),
+        (Using::BitBlt, Some(Using::PrintWindow))
+    );
+}
@@ -0,0 +1,203 @@
+use crate::prelude::*;
+
+const HWND: isize = 42;
+
+// 4x2 Bgra8, blue channel = x, green channel = y
+fn frame(alpha: u8) -> RgbBuf {
+    let mut pixels = Vec::new();
+    for y in 0..2 {
+        for x in 0..4 {
+            pixels.extend([x, y, 0, alpha]);
+        }
+    }
+    RgbBuf::new(pixels, 4, 2, PixelFormat::Bgra8).unwrap()
+}
+
+fn mock() -> MockBackend {
+    let mut m = MockBackend::new();
+    m.add_window(HWND, "mock window")
+        .push_frame(Target::Window(HWND), frame(0));
+    m
+}
+
+#[test]
+fn mock_targets() {
+    let names = mock().targets().unwrap();
+    assert_eq!(names.len(), 1);
+    assert_eq!(
+        (names[0].hwnd, names[0].window_name.as_str()),
+        (HWND, "mock window")
+    );
+}
+
+#[test]
+fn capture_with_defaults() {
+    let b = CaptureRequest::window(HWND)
+        .capture_with(&mut mock())
+        .unwrap();
+    assert_eq!((b.width, b.height, b.format), (4, 2, PixelFormat::Rgba8));
+    // swapped to rgba and made opaque
+    assert_eq!(&b.pixels[4..8], &[0, 0, 1, 255]);
+}
+
+#[test]
+fn capture_with_region_and_format() {
+    let b = CaptureRequest::window(HWND)
+        .region(Region::new(1, 1, 2, 1))
+        .format(PixelFormat::Bgra8)
+        .alpha(AlphaMode::Preserve)
+        .capture_with(&mut mock())
+        .unwrap();
+    assert_eq!(b.pixels, vec![1, 1, 0, 0, 2, 1, 0, 0]);
+}
+
+#[test]
+fn capture_with_policy() {
+    let req = CaptureRequest::window(HWND).region(Region::new(3, 0, 2, 1));
+    assert!(matches!(
+        req.capture_with(&mut mock()),
+        Err(Error::Buffer(BufError::OutOfBounds(_)))
+    ));
+    let b = req
+        .region_policy(RegionPolicy::Clamp)
+        .capture_with(&mut mock())
+        .unwrap();
+    assert_eq!((b.width, b.height), (1, 1));
+    let b = req
+        .region_policy(RegionPolicy::PadWithColor([9, 9, 9, 9]))
+        .format(PixelFormat::Bgra8)
+        .capture_with(&mut mock())
+        .unwrap();
+    assert_eq!(b.pixels, vec![3, 0, 0, 255, 9, 9, 9, 9]);
+}
+
+#[test]
+fn capture_with_unmirror() {
+    let mut m = mock();
+    m.set_mirrored(Target::Window(HWND));
+    let req = CaptureRequest::window(HWND)
+        .region(Region::new(0, 0, 1, 1))
+        .format(PixelFormat::Bgra8);
+    let b = req.capture_with(&mut m).unwrap();
+    assert_eq!(b.pixels, vec![0, 0, 0, 255]);
+    let b = req.unmirror(true).capture_with(&mut m).unwrap();
+    assert_eq!(b.pixels, vec![3, 0, 0, 255]);
+}
+
+#[test]
+fn mock_serves_frames_in_order() {
+    let mut m = mock();
+    let target = Target::Window(HWND);
+    m.push_frame(target, frame(1)).push_frame(target, frame(2));
+    let req = CaptureRequest::window(HWND).alpha(AlphaMode::Preserve);
+    let alphas: Vec<u8> = (0..4)
+        .map(|_| req.capture_with(&mut m).unwrap().pixels[3])
+        .collect();
+    assert_eq!(alphas, vec![0, 1, 2, 2]);
+    assert_eq!(m.grabs(), 4);
+    assert!(matches!(
+        CaptureRequest::display().capture_with(&mut m),
+        Err(Error::TargetNotFound(Target::Display))
+    ));
+}
+
+// only the required methods, grab_to_slice falls back to grab
+struct GrabOnly(MockBackend);
+
+impl CaptureBackend for GrabOnly {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.0.targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        self.0.geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        self.0.grab(target, area, src)
+    }
+}
+
+// 4x2 with varied color and alpha, mirrored
+fn mixed(format: PixelFormat) -> MockBackend {
+    let bgra: Vec<u8> = (0..32).map(|i| (i * 37 % 256) as u8).collect();
+    let frame = RgbBuf::new(bgra, 4, 2, PixelFormat::Bgra8)
+        .unwrap()
+        .convert(format)
+        .unwrap();
+    let mut m = MockBackend::new();
+    m.push_frame(Target::Window(HWND), frame)
+        .set_mirrored(Target::Window(HWND));
+    m
+}
+
+#[test]
+fn capture_to_slice_matches_capture_with() {
+    let req = CaptureRequest::window(HWND);
+    let requests = [
+        req,
+        req.format(PixelFormat::Bgra8).alpha(AlphaMode::Preserve),
+        req.alpha(AlphaMode::PremultipliedToStraight),
+        req.region(Region::new(1, 0, 2, 2)).unmirror(true),
+        req.region(Region::new(2, 1, 3, 2))
+            .region_policy(RegionPolicy::PadWithColor([1, 2, 3, 255])),
+        req.region(Region::new(5, 5, 2, 1))
+            .region_policy(RegionPolicy::PadWithColor([1, 2, 3, 255])),
+        req.format(PixelFormat::Rgb8),
+        req.format(PixelFormat::Gray8)
+            .region(Region::new(0, 1, 3, 1)),
+    ];
+    for format in [PixelFormat::Bgra8, PixelFormat::Rgba8, PixelFormat::Rgb8] {
+        for r in requests {
+            let expected = r.capture_with(&mut mixed(format)).unwrap();
+            let row = (expected.width * expected.format.bytes_per_pixel() as u32) as usize;
+            let stride = row + 3;
+            let mut dst = vec![0xee; stride * expected.height as usize];
+
+            let mut slice = |backend: &mut dyn CaptureBackend| {
+                dst.fill(0xee);
+                let size = r.capture_to_slice_with(backend, &mut dst, stride).unwrap();
+                assert_eq!(size, (expected.width, expected.height));
+                let rows: Vec<u8> = dst.chunks(stride).flat_map(|l| l[..row].to_vec()).collect();
+                assert_eq!(rows, expected.pixels);
+                // padding between rows is left alone
+                assert!(dst.chunks(stride).all(|l| l[row..] == [0xee; 3]));
+            };
+            slice(&mut mixed(format));
+            slice(&mut GrabOnly(mixed(format)));
+        }
+    }
+}
+
+#[test]
+fn capture_to_slice_checks_size() {
+    let req = CaptureRequest::window(HWND);
+    let mut dst = vec![0; 64];
+    assert_eq!(
+        req.capture_to_slice_with(&mut mock(), &mut dst, 16)
+            .unwrap(),
+        (4, 2)
+    );
+    // the last row needs no padding
+    assert!(req
+        .capture_to_slice_with(&mut mock(), &mut dst[..16 + 20 + 16], 20)
+        .is_ok());
+    assert!(matches!(
+        req.capture_to_slice_with(&mut mock(), &mut dst[..31], 16),
+        Err(Error::Buffer(BufError::TooSmall {
+            expected: 32,
+            actual: 31
+        }))
+    ));
+    assert!(matches!(
+        req.capture_to_slice_with(&mut mock(), &mut dst, 12),
+        Err(Error::Buffer(BufError::Stride {
+            stride: 12,
+            row: 16
+        }))
+    ));
+    let mut m = mock();
+    let _ = req.capture_to_slice_with(&mut m, &mut dst[..1], 16);
+    // nothing grabbed into memory that is too small
+    assert_eq!(m.grabs(), 0);
+}
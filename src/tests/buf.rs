@@ -0,0 +1,136 @@
+use crate::prelude::*;
+
+fn bgra() -> RgbBuf {
+    RgbBuf::new(
+        vec![10, 20, 30, 255, 0, 0, 255, 128],
+        2,
+        1,
+        PixelFormat::Bgra8,
+    )
+    .unwrap()
+}
+
+#[test]
+fn convert_swaps_channels() {
+    let b = bgra().convert(PixelFormat::Rgba8).unwrap();
+    assert_eq!(b.format, PixelFormat::Rgba8);
+    assert_eq!(b.pixels, vec![30, 20, 10, 255, 255, 0, 0, 128]);
+    let b = b.convert(PixelFormat::Bgra8).unwrap();
+    assert_eq!(b.pixels, bgra().pixels);
+}
+
+#[test]
+fn convert_changes_depth() {
+    let b = bgra().convert(PixelFormat::Rgb8).unwrap();
+    assert_eq!(b.pixels, vec![30, 20, 10, 255, 0, 0]);
+    let g = b.convert(PixelFormat::Gray8).unwrap();
+    assert_eq!(g.pixels, vec![22, 77]);
+    let b = g.convert(PixelFormat::Bgra8).unwrap();
+    assert_eq!(b.pixels, vec![22, 22, 22, 255, 77, 77, 77, 255]);
+}
+
+#[test]
+fn convert_checks_size() {
+    let mut b = bgra();
+    b.pixels.pop();
+    assert_eq!(
+        b.convert(PixelFormat::Rgb8).unwrap_err(),
+        BufError::SizeMismatch {
+            expected: 8,
+            actual: 7
+        }
+    );
+    assert!(RgbBuf::new(vec![0; 5], 2, 1, PixelFormat::Rgb8).is_err());
+}
+
+fn gray_4x3() -> RgbBuf {
+    RgbBuf::new((0..12).collect(), 4, 3, PixelFormat::Gray8).unwrap()
+}
+
+#[test]
+fn view_borrows_region() {
+    let b = gray_4x3();
+    let v = b.view(Region::new(1, 1, 2, 2)).unwrap();
+    assert_eq!((v.offset, v.stride), (5, 4));
+    assert_eq!(
+        v.rows().collect::<Vec<_>>(),
+        vec![&[5, 6][..], &[9, 10][..]]
+    );
+    let o = v.view(Region::new(1, 0, 1, 2)).unwrap().to_owned();
+    assert_eq!((o.width, o.height, o.pixels), (1, 2, vec![6, 10]));
+}
+
+#[test]
+fn view_checks_bounds() {
+    let b = gray_4x3();
+    let r = Region::new(3, 0, 2, 1);
+    assert_eq!(b.view(r).unwrap_err(), BufError::OutOfBounds(r));
+    assert!(b.view(Region::new(-1, 0, 1, 1)).is_err());
+    assert!(b.view(Region::new(0, 0, 4, 3)).is_ok());
+    assert!(RgbBufView::new(&b.pixels, 4, 4, 2, 4, PixelFormat::Gray8).is_ok());
+    assert!(RgbBufView::new(&b.pixels, 4, 4, 3, 4, PixelFormat::Gray8).is_err());
+    // offset + stride * (height - 1) + row overflows usize
+    assert_eq!(
+        RgbBufView::new(&b.pixels, usize::MAX, 1, 2, 1, PixelFormat::Gray8).unwrap_err(),
+        BufError::SizeMismatch {
+            expected: usize::MAX,
+            actual: 12
+        }
+    );
+    assert!(RgbBufView::new(&b.pixels, 0, 1, 3, usize::MAX, PixelFormat::Gray8).is_err());
+    // the same fields set by hand
+    let bad = RgbBufView {
+        stride: usize::MAX,
+        ..b.as_view()
+    };
+    assert!(bad.check().is_err());
+    assert!(bad.view(Region::new(0, 1, 1, 1)).is_err());
+    assert_eq!(bad.rows().count(), 1);
+}
+
+#[test]
+fn copy_to_slice_uses_stride() {
+    let b = gray_4x3();
+    let mut dst = [0xff; 6 + 6 + 4];
+    b.copy_to_slice(&mut dst, 6).unwrap();
+    assert_eq!(dst[..4], b.pixels[..4]);
+    assert_eq!(dst[4..6], [0xff; 2]);
+    assert_eq!(dst[12..], b.pixels[8..]);
+
+    let v = b.view(Region::new(1, 1, 2, 2)).unwrap();
+    let mut dst = [0; 4];
+    v.copy_to_slice(&mut dst, 2).unwrap();
+    assert_eq!(dst, [5, 6, 9, 10]);
+}
+
+#[test]
+fn copy_to_slice_checks_size() {
+    let b = gray_4x3();
+    let mut dst = [0; 16];
+    assert_eq!(
+        b.copy_to_slice(&mut dst[..11], 4),
+        Err(BufError::TooSmall {
+            expected: 12,
+            actual: 11
+        })
+    );
+    assert_eq!(
+        b.copy_to_slice(&mut dst, 3),
+        Err(BufError::Stride { stride: 3, row: 4 })
+    );
+    assert_eq!(
+        b.copy_to_slice(&mut dst, usize::MAX),
+        Err(BufError::TooSmall {
+            expected: usize::MAX,
+            actual: 16
+        })
+    );
+    assert_eq!(
+        BufError::TooSmall {
+            expected: 12,
+            actual: 11
+        }
+        .to_string(),
+        "buffer is 11 bytes, frame needs 12"
+    );
+}
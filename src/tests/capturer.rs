@@ -0,0 +1,134 @@
+use crate::prelude::*;
+
+const HWND: isize = 7;
+
+// Bgra8, blue channel = x, green channel = y, red channel = n
+fn frame(width: u32, height: u32, n: u8) -> RgbBuf {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            pixels.extend([x as u8, y as u8, n, 0]);
+        }
+    }
+    RgbBuf::new(pixels, width, height, PixelFormat::Bgra8).unwrap()
+}
+
+fn capturer(frames: Vec<RgbBuf>, request: CaptureRequest) -> WindowCapturer<MockBackend> {
+    let mut mock = MockBackend::new();
+    for f in frames {
+        mock.push_frame(Target::Window(HWND), f);
+    }
+    WindowCapturer::with_backend(mock, request)
+}
+
+#[test]
+fn capture_into_reuses_buffer() {
+    let mut c = capturer(
+        vec![frame(4, 2, 1), frame(4, 2, 2), frame(4, 2, 3)],
+        CaptureRequest::window(HWND),
+    );
+    let mut out = RgbBuf::new(Vec::new(), 0, 0, PixelFormat::Rgba8).unwrap();
+    c.capture_into(&mut out).unwrap();
+    let ptr = out.pixels.as_ptr();
+    for n in [2, 3] {
+        c.capture_into(&mut out).unwrap();
+        assert_eq!(out.pixels.as_ptr(), ptr);
+        assert_eq!(
+            (out.width, out.height, out.format),
+            (4, 2, PixelFormat::Rgba8)
+        );
+        // rgba, opaque
+        assert_eq!(&out.pixels[4..8], &[n, 0, 1, 255]);
+    }
+    assert_eq!(c.backend().grabs(), 3);
+}
+
+#[test]
+fn capture_follows_size_changes() {
+    let mut c = capturer(
+        vec![frame(4, 2, 1), frame(2, 3, 2)],
+        CaptureRequest::window(HWND).format(PixelFormat::Bgra8),
+    );
+    let first = c.capture().unwrap();
+    assert_eq!((first.width, first.height), (4, 2));
+    assert_eq!(first.pixels.len(), 4 * 2 * 4);
+
+    let second = c.capture().unwrap();
+    assert_eq!((second.width, second.height), (2, 3));
+    assert_eq!(
+        second.pixels,
+        frame(2, 3, 2).apply_alpha(AlphaMode::Opaque).pixels
+    );
+}
+
+#[test]
+fn capture_into_crops() {
+    let mut c = capturer(
+        vec![frame(4, 2, 1)],
+        CaptureRequest::window(HWND)
+            .region(Region::new(1, 1, 2, 1))
+            .format(PixelFormat::Rgb8),
+    );
+    let mut out = frame(8, 8, 0);
+    c.capture_into(&mut out).unwrap();
+    assert_eq!((out.width, out.height), (2, 1));
+    assert_eq!(out.pixels, [1, 1, 1, 1, 1, 2]);
+}
+
+#[test]
+fn capture_into_error_keeps_buffer() {
+    let mut c = capturer(Vec::new(), CaptureRequest::window(HWND));
+    let mut out = frame(4, 2, 1);
+    assert!(matches!(
+        c.capture_into(&mut out),
+        Err(Error::TargetNotFound(Target::Window(HWND)))
+    ));
+    assert_eq!(out.pixels.len(), 4 * 2 * 4);
+}
+
+#[test]
+fn capture_to_slice_writes_rows() {
+    let mut c = capturer(
+        vec![frame(3, 2, 1), frame(3, 2, 2)],
+        CaptureRequest::window(HWND).region(Region::new(1, 0, 2, 2)),
+    );
+    let mut dst = [0; 12 + 8];
+    assert_eq!(c.capture_to_slice(&mut dst, 12).unwrap(), (2, 2));
+    assert_eq!(dst[..8], [1, 0, 1, 255, 1, 0, 2, 255]);
+    assert_eq!(dst[12..], [1, 1, 1, 255, 1, 1, 2, 255]);
+    assert_eq!(dst[8..12], [0; 4]);
+    // rgb goes through the capturer's own frame
+    let mut c = capturer(
+        vec![frame(2, 1, 5)],
+        CaptureRequest::window(HWND).format(PixelFormat::Rgb8),
+    );
+    let mut dst = [0; 6];
+    assert_eq!(c.capture_to_slice(&mut dst, 6).unwrap(), (2, 1));
+    assert_eq!(dst, [5, 0, 0, 5, 0, 1]);
+}
+
+#[test]
+fn capture_pooled_reuses_returned_buffers() {
+    let pool = BufPool::new(1);
+    let mut c = capturer(vec![frame(4, 2, 1)], CaptureRequest::window(HWND));
+    let frame = c.capture_pooled(&pool).unwrap();
+    let ptr = frame.pixels.as_ptr();
+    pool.put(frame);
+    assert_eq!(c.capture_pooled(&pool).unwrap().pixels.as_ptr(), ptr);
+}
+
+#[test]
+fn capturer_remembers_auto_method() {
+    let mut c = capturer(
+        vec![frame(4, 2, 1)],
+        CaptureRequest::window(HWND).using(Using::Auto),
+    );
+    c.backend().black_with(Target::Window(HWND), Using::BitBlt);
+    for grabs in [2, 3] {
+        c.capture().unwrap();
+        assert_eq!(c.backend().grabs(), grabs);
+    }
+    c.forget_auto();
+    c.capture().unwrap();
+    assert_eq!(c.backend().grabs(), 5);
+}
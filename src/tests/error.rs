@@ -0,0 +1,26 @@
+use std::error::Error as _;
+
+use crate::prelude::*;
+
+#[test]
+fn error_chain() {
+    let e = Error::from(BufError::SizeMismatch {
+        expected: 8,
+        actual: 7,
+    });
+    assert_eq!(e.to_string(), "pixel buffer is 7 bytes, expected 8");
+    assert!(e.source().is_some());
+    assert_eq!(e.code(), None);
+    assert_eq!(
+        Error::GetSystemMetrics.to_string(),
+        "GetSystemMetrics returned zero"
+    );
+}
+
+#[cfg(not(any(windows, all(unix, feature = "x11"))))]
+#[test]
+fn unsupported_platform() {
+    assert!(matches!(capture_display(), Err(Error::Unsupported)));
+    assert!(matches!(window_list(), Err(Error::Unsupported)));
+    assert!(matches!(find_window("cmd"), Err(Error::Unsupported)));
+}
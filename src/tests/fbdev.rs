@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::prelude::*;
+
+// synthetic framebuffer memory in a temp file, removed on drop
+struct FbFile(PathBuf);
+
+impl FbFile {
+    fn new(name: &str, data: &[u8]) -> FbFile {
+        let path =
+            std::env::temp_dir().join(format!("win-screenshot-fb-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        FbFile(path)
+    }
+}
+
+impl Drop for FbFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn field(offset: u32, length: u32) -> FbBitfield {
+    FbBitfield { offset, length }
+}
+
+fn info(bits_per_pixel: u32, line_length: u32) -> FbInfo {
+    FbInfo {
+        width: 2,
+        height: 2,
+        xoffset: 0,
+        yoffset: 0,
+        bits_per_pixel,
+        line_length,
+        red: field(16, 8),
+        green: field(8, 8),
+        blue: field(0, 8),
+    }
+}
+
+fn capture(file: &FbFile, info: FbInfo, request: CaptureRequest) -> RgbBuf {
+    let mut fb = FbdevBackend::with_info(&file.0, info).unwrap();
+    request
+        .format(PixelFormat::Rgb8)
+        .capture_with(&mut fb)
+        .unwrap()
+}
+
+#[test]
+fn var_screeninfo_matches_kernel() {
+    // FBIOGET_VSCREENINFO writes this many bytes
+    assert_eq!(std::mem::size_of::<crate::fbdev::FbVarScreeninfo>(), 160);
+}
+
+#[test]
+fn fbdev_rgb565_padded() {
+    // red, green / blue, white, 4 bytes of padding per line
+    let data = [
+        0x00, 0xf8, 0xe0, 0x07, 0xaa, 0xaa, 0xaa, 0xaa, //
+        0x1f, 0x00, 0xff, 0xff, 0xaa, 0xaa, 0xaa, 0xaa,
+    ];
+    let file = FbFile::new("rgb565", &data);
+    let info = FbInfo {
+        red: field(11, 5),
+        green: field(5, 6),
+        blue: field(0, 5),
+        ..info(16, 8)
+    };
+    let b = capture(&file, info, CaptureRequest::display());
+    assert_eq!((b.width, b.height), (2, 2));
+    assert_eq!(b.pixels, [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+}
+
+#[test]
+fn fbdev_24bpp() {
+    // bgr byte order, 1 byte of padding per line
+    let data = [1, 2, 3, 4, 5, 6, 0, 7, 8, 9, 10, 11, 12, 0];
+    let file = FbFile::new("24bpp", &data);
+    let b = capture(&file, info(24, 7), CaptureRequest::display());
+    assert_eq!(b.pixels, [3, 2, 1, 6, 5, 4, 9, 8, 7, 12, 11, 10]);
+}
+
+#[test]
+fn fbdev_32bpp_panned_crop() {
+    // 3x3 virtual screen panned to the bottom right 2x2, blue channel = index
+    let data: Vec<u8> = (0..9).flat_map(|i| [i, 0, 0, 0]).collect();
+    let file = FbFile::new("32bpp", &data);
+    let info = FbInfo {
+        xoffset: 1,
+        yoffset: 1,
+        ..info(32, 12)
+    };
+    let b = capture(&file, info, CaptureRequest::display());
+    assert_eq!(b.pixels, [0, 0, 4, 0, 0, 5, 0, 0, 7, 0, 0, 8]);
+
+    let b = capture(
+        &file,
+        info,
+        CaptureRequest::display().region(Region::new(1, 0, 1, 2)),
+    );
+    assert_eq!(b.pixels, [0, 0, 5, 0, 0, 8]);
+}
+
+#[test]
+fn fbdev_errors() {
+    let file = FbFile::new("short", &[0; 10]);
+    // plain files don't answer the screen info ioctls
+    assert!(matches!(FbdevBackend::open(&file.0), Err(Error::Fbdev(_))));
+
+    let mut fb = FbdevBackend::with_info(&file.0, info(32, 8)).unwrap();
+    assert!(matches!(
+        CaptureRequest::display().capture_with(&mut fb),
+        Err(Error::Fbdev(_))
+    ));
+    assert!(matches!(
+        CaptureRequest::window(1).capture_with(&mut fb),
+        Err(Error::TargetNotFound(Target::Window(1)))
+    ));
+}
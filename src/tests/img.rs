@@ -0,0 +1,48 @@
+use crate::prelude::*;
+use image::{DynamicImage, GrayImage, RgbImage, RgbaImage};
+
+fn bgra() -> RgbBuf {
+    RgbBuf::new(vec![1, 2, 3, 4, 5, 6, 7, 8], 2, 1, PixelFormat::Bgra8).unwrap()
+}
+
+#[test]
+fn image_from_buf() {
+    let img = RgbaImage::try_from(bgra()).unwrap();
+    assert_eq!(img.into_raw(), vec![3, 2, 1, 4, 7, 6, 5, 8]);
+    let img = RgbImage::try_from(bgra()).unwrap();
+    assert_eq!(img.into_raw(), vec![3, 2, 1, 7, 6, 5]);
+    let gray = bgra().convert(PixelFormat::Gray8).unwrap();
+    assert!(matches!(
+        DynamicImage::try_from(gray).unwrap(),
+        DynamicImage::ImageLuma8(_)
+    ));
+    let mut bad = bgra();
+    bad.width = 3;
+    assert!(RgbaImage::try_from(bad).is_err());
+}
+
+#[test]
+fn buf_from_image() {
+    let b = RgbBuf::from(GrayImage::from_raw(2, 1, vec![9, 10]).unwrap());
+    assert_eq!(
+        (b.format, b.width, b.pixels),
+        (PixelFormat::Gray8, 2, vec![9, 10])
+    );
+    let b = RgbBuf::from(DynamicImage::new_rgb16(3, 2));
+    assert_eq!((b.format, b.width, b.height), (PixelFormat::Rgba8, 3, 2));
+}
+
+#[test]
+fn save_picks_encoder() {
+    // per process, parallel test runs don't share files
+    let dir = std::env::temp_dir();
+    let name = format!("win-screenshot-save-{}", std::process::id());
+    for ext in ["png", "jpg", "bmp"] {
+        let path = dir.join(format!("{}.{}", name, ext));
+        bgra().save(&path).unwrap();
+        let img = image::open(&path).unwrap();
+        assert_eq!((img.width(), img.height()), (2, 1));
+        std::fs::remove_file(&path).unwrap();
+    }
+    assert!(bgra().save(dir.join(format!("{}.unknown", name))).is_err());
+}
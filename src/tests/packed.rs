@@ -0,0 +1,84 @@
+use crate::buf::{BufError, PixelFormat};
+use crate::packed::Packed;
+
+const BGRX: Packed = Packed {
+    bits_per_pixel: 32,
+    red_mask: 0xff0000,
+    green_mask: 0xff00,
+    blue_mask: 0xff,
+    big_endian: false,
+};
+
+const RGB565: Packed = Packed {
+    bits_per_pixel: 16,
+    red_mask: 0xf800,
+    green_mask: 0x07e0,
+    blue_mask: 0x001f,
+    big_endian: false,
+};
+
+#[cfg(all(unix, feature = "x11"))]
+#[test]
+fn packed_stride() {
+    assert_eq!(BGRX.stride(3, 32), 12);
+    assert_eq!(RGB565.stride(3, 32), 8);
+    assert_eq!(RGB565.stride(3, 8), 6);
+}
+
+#[test]
+fn unpack_bgrx_with_padding() {
+    // 1x2, 8 byte stride with junk after each pixel
+    let data = [1, 2, 3, 0, 9, 9, 9, 9, 4, 5, 6, 0];
+    let b = BGRX.unpack(&data, 1, 2, 8).unwrap();
+    assert_eq!(b.format, PixelFormat::Bgra8);
+    assert_eq!(b.pixels, [1, 2, 3, 255, 4, 5, 6, 255]);
+}
+
+#[test]
+fn unpack_rgb565() {
+    // red, green, blue, white
+    let data = [0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0xff, 0xff];
+    let b = RGB565.unpack(&data, 4, 1, 8).unwrap();
+    assert_eq!(
+        b.pixels,
+        [0, 0, 255, 255, 0, 255, 0, 255, 255, 0, 0, 255, 255, 255, 255, 255]
+    );
+}
+
+#[test]
+fn unpack_24bpp_big_endian() {
+    let p = Packed {
+        bits_per_pixel: 24,
+        big_endian: true,
+        ..BGRX
+    };
+    let b = p.unpack(&[10, 20, 30], 1, 1, 3).unwrap();
+    // r, g, b read most significant first
+    assert_eq!(b.pixels, [30, 20, 10, 255]);
+}
+
+#[test]
+fn unpack_errors() {
+    assert!(matches!(
+        BGRX.unpack(&[0; 7], 1, 2, 4),
+        Err(BufError::SizeMismatch {
+            expected: 8,
+            actual: 7
+        })
+    ));
+    assert!(matches!(
+        BGRX.unpack(&[0; 8], 1, 3, usize::MAX),
+        Err(BufError::SizeMismatch {
+            expected: usize::MAX,
+            actual: 8
+        })
+    ));
+    let p = Packed {
+        bits_per_pixel: 4,
+        ..BGRX
+    };
+    assert!(matches!(
+        p.unpack(&[0; 4], 1, 1, 4),
+        Err(BufError::BitsPerPixel(4))
+    ));
+}
@@ -0,0 +1,63 @@
+use crate::prelude::*;
+
+const HWND: isize = 3;
+
+fn mock() -> MockBackend {
+    let mut m = MockBackend::new();
+    m.push_frame(
+        Target::Window(HWND),
+        RgbBuf::new(vec![1; 3 * 2 * 4], 3, 2, PixelFormat::Bgra8).unwrap(),
+    );
+    m
+}
+
+#[test]
+fn pool_recycles_allocations() {
+    let pool = BufPool::new(2);
+    assert!(pool.is_empty());
+    let req = CaptureRequest::window(HWND);
+    let mut m = mock();
+
+    let first = req.capture_pooled_with(&mut m, &pool).unwrap();
+    assert_eq!((first.width, first.height), (3, 2));
+    let ptr = first.pixels.as_ptr();
+    // returned from another thread through a clone
+    let other = pool.clone();
+    std::thread::spawn(move || other.put(first)).join().unwrap();
+    assert_eq!(pool.len(), 1);
+
+    let second = req.capture_pooled_with(&mut m, &pool).unwrap();
+    assert_eq!(second.pixels.as_ptr(), ptr);
+    assert!(pool.is_empty());
+    assert_eq!(second.pixels, [1, 1, 1, 255].repeat(6));
+}
+
+#[test]
+fn pool_keeps_at_most_max() {
+    let pool = BufPool::new(1);
+    pool.put(RgbBuf::new(vec![7; 16], 2, 2, PixelFormat::Bgra8).unwrap());
+    let extra = RgbBuf::new(vec![0; 4], 1, 1, PixelFormat::Bgra8).unwrap();
+    pool.put(extra);
+    assert_eq!(pool.len(), 1);
+    // a taken buffer is an empty frame whatever it held before
+    let b = pool.take();
+    assert_eq!((b.width, b.height, b.pixels.len()), (0, 0, 0));
+    assert!(b.pixels.capacity() >= 16);
+}
+
+#[test]
+fn default_pool_keeps_buffers() {
+    let pool = BufPool::default();
+    for _ in 0..5 {
+        pool.put(RgbBuf::new(vec![0; 4], 1, 1, PixelFormat::Bgra8).unwrap());
+    }
+    assert_eq!(pool.len(), 3);
+}
+
+#[test]
+fn pool_gets_buffer_back_on_error() {
+    let pool = BufPool::new(4);
+    let req = CaptureRequest::display();
+    assert!(req.capture_pooled_with(&mut mock(), &pool).is_err());
+    assert_eq!(pool.len(), 1);
+}
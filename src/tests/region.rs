@@ -0,0 +1,83 @@
+use crate::prelude::*;
+
+#[test]
+fn region_from_crop() {
+    assert_eq!(
+        Region::from_crop(None, None, 10, 8),
+        Region::new(0, 0, 10, 8)
+    );
+    assert_eq!(
+        Region::from_crop(Some([2, 3]), None, 10, 8),
+        Region::new(2, 3, 8, 5)
+    );
+    assert_eq!(
+        Region::from_crop(None, Some([4, 4]), 10, 8),
+        Region::new(0, 0, 4, 4)
+    );
+}
+
+#[test]
+fn region_intersect() {
+    let a = Region::new(0, 0, 10, 10);
+    assert_eq!(
+        a.intersect(&Region::new(5, -5, 10, 10)),
+        Some(Region::new(5, 0, 5, 5))
+    );
+    assert_eq!(a.intersect(&Region::new(10, 0, 5, 5)), None);
+    assert_eq!(a.intersect(&Region::new(i32::MAX, 0, i32::MAX, 5)), None);
+}
+
+#[test]
+fn region_fit_error() {
+    let r = Region::new(2, 2, 4, 4);
+    let fit = r.fit(10, 10, RegionPolicy::Error).unwrap();
+    assert_eq!((fit.src, fit.width, fit.height), (r, 4, 4));
+    let r = Region::new(8, 2, 4, 4);
+    assert_eq!(
+        r.fit(10, 10, RegionPolicy::Error),
+        Err(BufError::OutOfBounds(r))
+    );
+    let r = Region::new(0, 0, -1, 4);
+    assert_eq!(
+        r.fit(10, 10, RegionPolicy::Clamp),
+        Err(BufError::OutOfBounds(r))
+    );
+}
+
+#[test]
+fn region_fit_clamp() {
+    let fit = Region::new(-2, 8, 5, 5)
+        .fit(10, 10, RegionPolicy::Clamp)
+        .unwrap();
+    assert_eq!(fit.src, Region::new(0, 8, 3, 2));
+    assert_eq!(fit.dst, Region::new(0, 0, 3, 2));
+    assert_eq!((fit.width, fit.height), (3, 2));
+    let r = Region::new(20, 20, 5, 5);
+    assert_eq!(
+        r.fit(10, 10, RegionPolicy::Clamp),
+        Err(BufError::OutOfBounds(r))
+    );
+}
+
+#[test]
+fn region_fit_pad() {
+    let policy = RegionPolicy::PadWithColor([1, 2, 3, 4]);
+    let fit = Region::new(-2, 8, 5, 5).fit(10, 10, policy).unwrap();
+    assert_eq!(fit.src, Region::new(0, 8, 3, 2));
+    assert_eq!(fit.dst, Region::new(2, 0, 3, 2));
+    assert_eq!((fit.width, fit.height), (5, 5));
+    let fit = Region::new(20, 20, 2, 1).fit(10, 10, policy).unwrap();
+    assert_eq!((fit.src.width, fit.width, fit.height), (0, 2, 1));
+}
+
+#[test]
+fn fit_place_pads() {
+    let fit = Region::new(-1, 0, 3, 2)
+        .fit(1, 1, RegionPolicy::PadWithColor([9, 8, 7, 6]))
+        .unwrap();
+    let src = RgbBuf::new(vec![1, 2, 3], 1, 1, PixelFormat::Rgb8).unwrap();
+    let b = fit.place(src, [9, 8, 7, 6]);
+    assert_eq!((b.width, b.height), (3, 2));
+    assert_eq!(&b.pixels[..9], &[9, 8, 7, 1, 2, 3, 9, 8, 7]);
+    assert_eq!(&b.pixels[9..], [9, 8, 7].repeat(3).as_slice());
+}
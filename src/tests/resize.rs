@@ -0,0 +1,72 @@
+use crate::prelude::*;
+
+fn gray(width: u32, height: u32, pixels: Vec<u8>) -> RgbBuf {
+    RgbBuf::new(pixels, width, height, PixelFormat::Gray8).unwrap()
+}
+
+#[test]
+fn resize_nearest() {
+    let b = gray(4, 1, vec![0, 10, 20, 30]);
+    assert_eq!(
+        b.resize(2, 1, Filter::Nearest).unwrap().pixels,
+        vec![10, 30]
+    );
+    assert_eq!(
+        b.resize(8, 1, Filter::Nearest).unwrap().pixels,
+        vec![0, 0, 10, 10, 20, 20, 30, 30]
+    );
+}
+
+#[test]
+fn resize_bilinear() {
+    let b = gray(2, 2, vec![0, 100, 100, 200]);
+    let r = b.resize(4, 4, Filter::Bilinear).unwrap();
+    assert_eq!(&r.pixels[..4], &[0, 25, 75, 100]);
+    assert_eq!(&r.pixels[12..], &[100, 125, 175, 200]);
+    assert_eq!(b.resize(1, 1, Filter::Bilinear).unwrap().pixels, vec![100]);
+}
+
+#[test]
+fn resize_area() {
+    let b = gray(4, 2, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+    assert_eq!(b.resize(2, 1, Filter::Area).unwrap().pixels, vec![25, 45]);
+    assert_eq!(b.resize(1, 1, Filter::Area).unwrap().pixels, vec![35]);
+    let b = gray(3, 1, vec![0, 30, 60]);
+    assert_eq!(b.resize(2, 1, Filter::Area).unwrap().pixels, vec![10, 50]);
+}
+
+#[test]
+fn resize_keeps_channels() {
+    let b = RgbBuf::new(
+        vec![10, 20, 30, 255, 30, 40, 50, 255],
+        2,
+        1,
+        PixelFormat::Bgra8,
+    )
+    .unwrap();
+    let r = b.resize(1, 1, Filter::Area).unwrap();
+    assert_eq!(
+        (r.format, r.pixels),
+        (PixelFormat::Bgra8, vec![20, 30, 40, 255])
+    );
+    let r = b
+        .view(Region::new(1, 0, 1, 1))
+        .unwrap()
+        .resize(2, 2, Filter::Nearest)
+        .unwrap();
+    assert_eq!(r.pixels, [30, 40, 50, 255].repeat(4));
+    assert!(b.resize(0, 5, Filter::Bilinear).unwrap().pixels.is_empty());
+}
+
+#[test]
+fn resize_checks_size() {
+    let mut b = gray(2, 2, vec![0; 4]);
+    b.pixels.pop();
+    assert_eq!(
+        b.resize(1, 1, Filter::Nearest).unwrap_err(),
+        BufError::SizeMismatch {
+            expected: 4,
+            actual: 3
+        }
+    );
+}
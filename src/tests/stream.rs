@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use crate::prelude::*;
+
+const HWND: isize = 9;
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+// grabs take cost[i] of fake time, the last cost repeats
+struct Slow {
+    mock: MockBackend,
+    clock: FakeClock,
+    cost: Vec<Duration>,
+}
+
+impl CaptureBackend for Slow {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.mock.targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        self.mock.geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        let i = self.mock.grabs().min(self.cost.len() - 1);
+        self.clock.advance(self.cost[i]);
+        self.mock.grab(target, area, src)
+    }
+}
+
+fn stream(cost: Vec<Duration>) -> (CaptureStream<Slow, FakeClock>, FakeClock) {
+    let clock = FakeClock::new();
+    // a clock that is not at zero when the stream starts
+    clock.advance(ms(1000));
+    let mut mock = MockBackend::new();
+    for n in 0..3 {
+        let pixels = vec![n; 2 * 2 * 4];
+        let frame = RgbBuf::new(pixels, 2, 2, PixelFormat::Bgra8).unwrap();
+        mock.push_frame(Target::Window(HWND), frame);
+    }
+    let slow = Slow {
+        mock,
+        clock: clock.clone(),
+        cost,
+    };
+    let s =
+        CaptureStream::with_backend(slow, CaptureRequest::window(HWND), 50.0).clock(clock.clone());
+    (s, clock)
+}
+
+#[test]
+fn frames_follow_deadlines() {
+    let (s, clock) = stream(vec![ms(5), ms(7), ms(3)]);
+    assert_eq!(s.interval(), ms(20));
+    let frames: Vec<Frame> = s.take(4).map(|f| f.unwrap()).collect();
+    let stamps: Vec<Duration> = frames.iter().map(|f| f.timestamp).collect();
+    // grab time does not push later frames back
+    assert_eq!(stamps, [ms(0), ms(20), ms(40), ms(60)]);
+    assert_eq!(
+        frames.iter().map(|f| f.seq).collect::<Vec<_>>(),
+        [0, 1, 2, 3]
+    );
+    assert!(frames.iter().all(|f| f.dropped == 0));
+    // scripted frames in order, the last repeats
+    let firsts: Vec<u8> = frames.iter().map(|f| f.buf.pixels[0]).collect();
+    assert_eq!(firsts, [0, 1, 2, 2]);
+    assert_eq!(clock.now(), ms(1000 + 60 + 3));
+}
+
+#[test]
+fn slow_grabs_drop_slots() {
+    // the second grab takes two and a half slots
+    let (mut s, _clock) = stream(vec![ms(1), ms(50), ms(1)]);
+    let a = s.next().unwrap().unwrap();
+    let b = s.next().unwrap().unwrap();
+    let c = s.next().unwrap().unwrap();
+    let d = s.next().unwrap().unwrap();
+    assert_eq!((a.timestamp, a.dropped), (ms(0), 0));
+    assert_eq!((b.timestamp, b.dropped), (ms(20), 0));
+    // b finished at 70: the slot at 40 is gone, c goes out late in the one at 60
+    assert_eq!((c.timestamp, c.dropped, c.seq), (ms(70), 1, 2));
+    // back on the grid
+    assert_eq!((d.timestamp, d.dropped, d.seq), (ms(80), 0, 3));
+    assert_eq!(s.dropped(), 1);
+}
+
+#[test]
+fn errors_keep_the_pace() {
+    let clock = FakeClock::new();
+    let s = CaptureStream::with_backend(MockBackend::new(), CaptureRequest::display(), 10.0)
+        .clock(clock.clone());
+    let results: Vec<bool> = s.take(3).map(|f| f.is_err()).collect();
+    assert_eq!(results, [true; 3]);
+    assert_eq!(clock.now(), ms(200));
+}
+
+#[test]
+fn pooled_frames() {
+    let pool = BufPool::new(2);
+    let mut mock = MockBackend::new();
+    let frame = RgbBuf::new(vec![0; 4], 1, 1, PixelFormat::Bgra8).unwrap();
+    mock.push_frame(Target::Window(HWND), frame);
+    let mut s = CaptureStream::with_backend(mock, CaptureRequest::window(HWND), 50.0)
+        .clock(FakeClock::new())
+        .pool(pool.clone());
+    let first = s.next().unwrap().unwrap();
+    let ptr = first.buf.pixels.as_ptr();
+    pool.put(first.buf);
+    assert_eq!(s.next().unwrap().unwrap().buf.pixels.as_ptr(), ptr);
+}
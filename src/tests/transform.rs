@@ -0,0 +1,26 @@
+use crate::prelude::*;
+
+// 3x2, pixel value = index
+fn gray() -> RgbBuf {
+    RgbBuf::new(vec![0, 1, 2, 3, 4, 5], 3, 2, PixelFormat::Gray8).unwrap()
+}
+
+#[test]
+fn flip() {
+    assert_eq!(gray().flip_horizontal().pixels, vec![2, 1, 0, 5, 4, 3]);
+    assert_eq!(gray().flip_vertical().pixels, vec![3, 4, 5, 0, 1, 2]);
+    let b = RgbBuf::new(vec![1, 2, 3, 4, 5, 6], 2, 1, PixelFormat::Rgb8).unwrap();
+    assert_eq!(b.flip_horizontal().pixels, vec![4, 5, 6, 1, 2, 3]);
+}
+
+#[test]
+fn rotate() {
+    let r = gray().rotate90();
+    assert_eq!((r.width, r.height), (2, 3));
+    assert_eq!(r.pixels, vec![3, 0, 4, 1, 5, 2]);
+    assert_eq!(gray().rotate180().pixels, vec![5, 4, 3, 2, 1, 0]);
+    let r = gray().rotate270();
+    assert_eq!((r.width, r.height), (2, 3));
+    assert_eq!(r.pixels, vec![2, 5, 1, 4, 0, 3]);
+    assert_eq!(gray().rotate90().rotate270().pixels, gray().pixels);
+}
@@ -0,0 +1,423 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress};
+
+use crate::prelude::*;
+use crate::rfb;
+
+// minimal RFB 3.8 server for one client, script runs after ServerInit
+fn serve<F>(
+    width: u16,
+    height: u16,
+    password: Option<&'static str>,
+    script: F,
+) -> (SocketAddr, JoinHandle<()>)
+where
+    F: FnOnce(&mut TcpStream) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        s.write_all(rfb::VERSION).unwrap();
+        let mut version = [0; 12];
+        s.read_exact(&mut version).unwrap();
+        assert_eq!(&version, rfb::VERSION);
+
+        let security = match password {
+            Some(_) => rfb::SECURITY_VNC,
+            None => rfb::SECURITY_NONE,
+        };
+        s.write_all(&[1, security]).unwrap();
+        let mut chosen = [0; 1];
+        s.read_exact(&mut chosen).unwrap();
+        assert_eq!(chosen[0], security);
+        if let Some(password) = password {
+            let challenge = *b"0123456789abcdef";
+            s.write_all(&challenge).unwrap();
+            let mut response = [0; 16];
+            s.read_exact(&mut response).unwrap();
+            if response != rfb::vnc_auth(password, &challenge) {
+                s.write_all(&1u32.to_be_bytes()).unwrap();
+                s.write_all(&string("bad password")).unwrap();
+                return;
+            }
+        }
+        s.write_all(&0u32.to_be_bytes()).unwrap();
+
+        let mut shared = [0; 1];
+        s.read_exact(&mut shared).unwrap();
+        let mut init = Vec::new();
+        init.extend_from_slice(&width.to_be_bytes());
+        init.extend_from_slice(&height.to_be_bytes());
+        init.extend_from_slice(&rfb::PIXEL_FORMAT);
+        init.extend_from_slice(&string("test desktop"));
+        s.write_all(&init).unwrap();
+
+        // SetPixelFormat, then SetEncodings
+        let mut format = [0; 20];
+        s.read_exact(&mut format).unwrap();
+        assert_eq!(format[4..], rfb::PIXEL_FORMAT);
+        let mut encodings = [0; 4];
+        s.read_exact(&mut encodings).unwrap();
+        let mut list = vec![0; u16::from_be_bytes([encodings[2], encodings[3]]) as usize * 4];
+        s.read_exact(&mut list).unwrap();
+
+        script(&mut s);
+        // keep the socket open until the client hangs up
+        let _ = s.read_to_end(&mut Vec::new());
+    });
+    (addr, handle)
+}
+
+fn string(s: &str) -> Vec<u8> {
+    let mut b = (s.len() as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(s.as_bytes());
+    b
+}
+
+fn update(rects: &[(Region, i32, Vec<u8>)]) -> Vec<u8> {
+    let mut msg = vec![0, 0];
+    msg.extend_from_slice(&(rects.len() as u16).to_be_bytes());
+    for (r, encoding, data) in rects {
+        for v in [r.x, r.y, r.width, r.height] {
+            msg.extend_from_slice(&(v as u16).to_be_bytes());
+        }
+        msg.extend_from_slice(&encoding.to_be_bytes());
+        msg.extend_from_slice(data);
+    }
+    msg
+}
+
+// rgb pixels as the client pixel format, with a junk padding byte
+fn raw(pixels: &[[u8; 3]]) -> Vec<u8> {
+    pixels.iter().flat_map(|&[r, g, b]| [b, g, r, 0]).collect()
+}
+
+fn cpixel([r, g, b]: [u8; 3]) -> [u8; 3] {
+    [b, g, r]
+}
+
+fn zrle(z: &mut Compress, tiles: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(tiles.len() + 64);
+    z.compress_vec(tiles, &mut out, FlushCompress::Sync)
+        .unwrap();
+    let mut data = (out.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(&out);
+    data
+}
+
+fn rgb(buf: &RgbBuf) -> Vec<[u8; 3]> {
+    buf.pixels
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect()
+}
+
+fn display(vnc: &mut VncBackend) -> RgbBuf {
+    CaptureRequest::display()
+        .format(PixelFormat::Rgb8)
+        .capture_with(vnc)
+        .unwrap()
+}
+
+const R: [u8; 3] = [255, 0, 0];
+const G: [u8; 3] = [0, 255, 0];
+const B: [u8; 3] = [0, 0, 255];
+const W: [u8; 3] = [255, 255, 255];
+
+#[test]
+fn raw_and_copy_rect() {
+    let (next, wait) = mpsc::channel();
+    let (addr, server) = serve(3, 2, None, move |s| {
+        let full = Region::new(0, 0, 3, 2);
+        s.write_all(&update(&[(full, rfb::RAW, raw(&[R, G, B, W, W, W]))]))
+            .unwrap();
+        wait.recv().unwrap();
+        // copy the top left 2x1 to the bottom right, repaint the top right
+        let mut src = 0u16.to_be_bytes().to_vec();
+        src.extend_from_slice(&0u16.to_be_bytes());
+        s.write_all(&update(&[
+            (Region::new(1, 1, 2, 1), rfb::COPY_RECT, src),
+            (Region::new(2, 0, 1, 1), rfb::RAW, raw(&[G])),
+        ]))
+        .unwrap();
+    });
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    assert_eq!(vnc.name(), "test desktop");
+    assert_eq!(rgb(&display(&mut vnc)), [R, G, B, W, W, W]);
+
+    next.send(()).unwrap();
+    vnc.refresh().unwrap();
+    let buf = display(&mut vnc);
+    assert_eq!((buf.width, buf.height), (3, 2));
+    assert_eq!(rgb(&buf), [R, G, G, W, R, G]);
+    drop(vnc);
+    server.join().unwrap();
+}
+
+#[test]
+fn zrle_tiles() {
+    // 70x66 is four tiles: 64x64, 6x64, 64x2, 6x2
+    let (w, h) = (70, 66);
+    let mut expected = vec![W; w * h];
+    let (next, wait) = mpsc::channel();
+    let (addr, server) = serve(w as u16, h as u16, None, move |s| {
+        let mut z = Compress::new(Compression::default(), true);
+        let mut tiles = Vec::new();
+        // solid
+        tiles.push(1);
+        tiles.extend_from_slice(&cpixel(R));
+        // packed palette of 3, 2 bits per index, rows padded to a byte
+        tiles.push(3);
+        for c in [R, G, B] {
+            tiles.extend_from_slice(&cpixel(c));
+        }
+        tiles.extend_from_slice(&[0b0001_1000, 0b0110_0000].repeat(64));
+        // plain RLE: 100 green then 28 blue, 100 = 1 + 99
+        tiles.push(128);
+        tiles.extend_from_slice(&cpixel(G));
+        tiles.push(99);
+        tiles.extend_from_slice(&cpixel(B));
+        tiles.push(27);
+        // raw
+        tiles.push(0);
+        for i in 0..12 {
+            tiles.extend_from_slice(&cpixel([i, i, i]));
+        }
+        s.write_all(&update(&[(
+            Region::new(0, 0, w as i32, h as i32),
+            rfb::ZRLE,
+            zrle(&mut z, &tiles),
+        )]))
+        .unwrap();
+        wait.recv().unwrap();
+
+        // palette RLE over the same zlib stream: a run of 300 then single pixels
+        let mut tiles = vec![130];
+        tiles.extend_from_slice(&cpixel(B));
+        tiles.extend_from_slice(&cpixel(W));
+        tiles.extend_from_slice(&[128, 255, 44]);
+        tiles.extend_from_slice(&[1; 300]);
+        s.write_all(&update(&[(
+            Region::new(3, 3, 30, 20),
+            rfb::ZRLE,
+            zrle(&mut z, &tiles),
+        )]))
+        .unwrap();
+    });
+
+    for y in 0..h {
+        for x in 0..w {
+            expected[y * w + x] = match (x, y) {
+                (0..=63, 0..=63) => R,
+                (_, 0..=63) => [R, G, B][(x - 64) % 3],
+                (0..=63, _) => match (y - 64) * 64 + x {
+                    0..=99 => G,
+                    _ => B,
+                },
+                _ => {
+                    let i = ((y - 64) * 6 + x - 64) as u8;
+                    [i, i, i]
+                }
+            };
+        }
+    }
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    assert_eq!(rgb(&display(&mut vnc)), expected);
+
+    next.send(()).unwrap();
+    for y in 3..23 {
+        for x in 3..33 {
+            expected[y * w + x] = match (y - 3) * 30 + x - 3 {
+                0..=299 => B,
+                _ => W,
+            };
+        }
+    }
+    vnc.refresh().unwrap();
+    assert_eq!(rgb(&display(&mut vnc)), expected);
+    drop(vnc);
+    server.join().unwrap();
+}
+
+#[test]
+fn poll_applies_pushed_updates() {
+    let (addr, server) = serve(2, 1, None, |s| {
+        s.write_all(&update(&[(
+            Region::new(0, 0, 2, 1),
+            rfb::RAW,
+            raw(&[R, R]),
+        )]))
+        .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        // bell and clipboard are skipped on the way to the update
+        s.write_all(&[2]).unwrap();
+        s.write_all(&[3, 0, 0, 0]).unwrap();
+        s.write_all(&string("clip")).unwrap();
+        s.write_all(&update(&[(Region::new(1, 0, 1, 1), rfb::RAW, raw(&[B]))]))
+            .unwrap();
+    });
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    let start = Instant::now();
+    while rgb(&display(&mut vnc)) != [R, B] {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+    drop(vnc);
+    server.join().unwrap();
+}
+
+#[test]
+fn desktop_size() {
+    let (addr, server) = serve(2, 1, None, |s| {
+        s.write_all(&update(&[(
+            Region::new(0, 0, 2, 1),
+            rfb::RAW,
+            raw(&[R, R]),
+        )]))
+        .unwrap();
+        s.write_all(&update(&[
+            (Region::new(0, 0, 1, 2), rfb::DESKTOP_SIZE, Vec::new()),
+            (Region::new(0, 0, 1, 2), rfb::RAW, raw(&[G, B])),
+        ]))
+        .unwrap();
+    });
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    vnc.refresh().unwrap();
+    let buf = display(&mut vnc);
+    assert_eq!((buf.width, buf.height), (1, 2));
+    assert_eq!(rgb(&buf), [G, B]);
+    drop(vnc);
+    server.join().unwrap();
+}
+
+#[test]
+fn vnc_auth() {
+    let (addr, server) = serve(1, 1, Some("secret"), |s| {
+        s.write_all(&update(&[(Region::new(0, 0, 1, 1), rfb::RAW, raw(&[G]))]))
+            .unwrap();
+    });
+    let mut vnc = VncBackend::connect(addr, Some("secret")).unwrap();
+    assert_eq!(rgb(&display(&mut vnc)), [G]);
+    drop(vnc);
+    server.join().unwrap();
+
+    let (addr, server) = serve(1, 1, Some("secret"), |_| {});
+    match VncBackend::connect(addr, Some("wrong")) {
+        Err(Error::Vnc(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+            assert_eq!(e.to_string(), "bad password");
+        }
+        r => panic!("{:?}", r.map(|_| ())),
+    }
+    server.join().unwrap();
+}
+
+#[test]
+fn known_des_response() {
+    // DES-ECB of zeros keyed by "password" with every byte bit reversed
+    let block = [0xff, 0x97, 0x50, 0x2e, 0x94, 0x22, 0xf0, 0x89];
+    let response = rfb::vnc_auth("password", &[0; 16]);
+    assert_eq!(response[..8], block);
+    assert_eq!(response[8..], block);
+}
+
+#[test]
+fn display_only() {
+    let (addr, server) = serve(1, 1, None, |s| {
+        s.write_all(&update(&[(Region::new(0, 0, 1, 1), rfb::RAW, raw(&[G]))]))
+            .unwrap();
+    });
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    assert!(matches!(
+        CaptureRequest::window(1).capture_with(&mut vnc),
+        Err(Error::TargetNotFound(_))
+    ));
+    drop(vnc);
+    server.join().unwrap();
+}
+
+#[test]
+fn inflate_exactly_filled_output() {
+    // noise then zeros, grown until it inflates to exactly 4x its compressed
+    // size, the first guess for the output buffer, with the limit right there too
+    let mut seed = 1u32;
+    let noise: Vec<u8> = (0..300)
+        .map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        })
+        .collect();
+    for zeros in 0..4096 {
+        let mut plain = noise.clone();
+        plain.resize(noise.len() + zeros, 0);
+        let mut z = Compress::new(Compression::default(), true);
+        let packed = &zrle(&mut z, &plain)[4..];
+        if plain.len() != packed.len() * 4 {
+            continue;
+        }
+        let mut d = Decompress::new(true);
+        assert_eq!(rfb::inflate(&mut d, packed, plain.len()).unwrap(), plain);
+        return;
+    }
+    panic!("no input inflates to exactly 4x");
+}
+
+#[test]
+fn zrle_run_past_the_tile() {
+    let (addr, server) = serve(2, 1, None, |s| {
+        let mut z = Compress::new(Compression::default(), true);
+        // plain RLE run of 3 in a 2 pixel tile
+        let mut tiles = vec![128];
+        tiles.extend_from_slice(&cpixel(G));
+        tiles.push(2);
+        let _ = s.write_all(&update(&[(
+            Region::new(0, 0, 2, 1),
+            rfb::ZRLE,
+            zrle(&mut z, &tiles),
+        )]));
+    });
+    assert!(matches!(
+        VncBackend::connect(addr, None),
+        Err(Error::Vnc(e)) if e.kind() == io::ErrorKind::InvalidData
+    ));
+    server.join().unwrap();
+}
+
+#[test]
+fn inflate_stops_at_the_limit() {
+    let mut z = Compress::new(Compression::default(), true);
+    let packed = &zrle(&mut z, &[0; 100_000])[4..];
+    let mut d = Decompress::new(true);
+    assert!(rfb::inflate(&mut d, packed, 99_999).is_err());
+    assert_eq!(rfb::zrle_max_len(64, 64), 64 * 64 * 4 + 1 + 127 * 3);
+}
+
+#[test]
+fn huge_server_init() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        s.write_all(rfb::VERSION).unwrap();
+        s.read_exact(&mut [0; 12]).unwrap();
+        s.write_all(&[1, rfb::SECURITY_NONE]).unwrap();
+        s.read_exact(&mut [0; 1]).unwrap();
+        s.write_all(&[0; 4]).unwrap();
+        s.read_exact(&mut [0; 1]).unwrap();
+        let mut init = vec![255, 255, 255, 255];
+        init.extend_from_slice(&rfb::PIXEL_FORMAT);
+        init.extend_from_slice(&string("big"));
+        s.write_all(&init).unwrap();
+    });
+    assert!(matches!(
+        VncBackend::connect(addr, None),
+        Err(Error::Vnc(e)) if e.kind() == io::ErrorKind::InvalidData
+    ));
+    server.join().unwrap();
+}
@@ -0,0 +1,258 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use flate2::{Compress, Compression, Decompress, FlushDecompress};
+
+use crate::prelude::*;
+use crate::rfb::{self, ClientFormat};
+use crate::vnc_server::dirty;
+
+fn frame(width: u32, height: u32, f: impl Fn(u32, u32) -> [u8; 3]) -> RgbBuf {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            pixels.extend_from_slice(&f(x, y));
+        }
+    }
+    RgbBuf::new(pixels, width, height, PixelFormat::Rgb8).unwrap()
+}
+
+fn gradient(x: u32, y: u32) -> [u8; 3] {
+    [(x * 3) as u8, (y * 5) as u8, ((x + y) * 7) as u8]
+}
+
+// holds every grab after the first until the test drops the sender
+struct Gated {
+    mock: MockBackend,
+    gate: mpsc::Receiver<()>,
+}
+
+impl CaptureBackend for Gated {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.mock.targets()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        self.mock.geometry(target, area)
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        if self.mock.grabs() > 0 {
+            let _ = self.gate.recv();
+        }
+        self.mock.grab(target, area, src)
+    }
+}
+
+// serves frames to a single viewer on a loopback port
+fn serve(
+    frames: Vec<RgbBuf>,
+    password: Option<&str>,
+) -> (SocketAddr, JoinHandle<Result<(), Error>>, mpsc::Sender<()>) {
+    let mut server = VncServer::bind("127.0.0.1:0")
+        .unwrap()
+        .name("mock")
+        .interval(Duration::from_millis(5));
+    if let Some(p) = password {
+        server = server.password(p);
+    }
+    let addr = server.local_addr().unwrap();
+    let (release, gate) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let mut mock = MockBackend::new();
+        for f in frames {
+            mock.push_frame(Target::Display, f);
+        }
+        server.serve(Gated { mock, gate }, CaptureRequest::display())
+    });
+    (addr, handle, release)
+}
+
+fn display(vnc: &mut VncBackend) -> RgbBuf {
+    CaptureRequest::display()
+        .format(PixelFormat::Rgb8)
+        .capture_with(vnc)
+        .unwrap()
+}
+
+fn wait_for(vnc: &mut VncBackend, expected: &RgbBuf) {
+    let start = Instant::now();
+    loop {
+        let buf = display(vnc);
+        if (buf.width, buf.height, &buf.pixels)
+            == (expected.width, expected.height, &expected.pixels)
+        {
+            return;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "update never arrived"
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn viewer_follows_frames() {
+    // a white square moves in over the gradient
+    let square = |x, y| match (x, y) {
+        (100..=109, 60..=69) => [255, 255, 255],
+        _ => gradient(x, y),
+    };
+    let (addr, server, release) =
+        serve(vec![frame(150, 70, gradient), frame(150, 70, square)], None);
+
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    assert_eq!(vnc.name(), "mock");
+    assert_eq!(display(&mut vnc).pixels, frame(150, 70, gradient).pixels);
+    drop(release);
+    wait_for(&mut vnc, &frame(150, 70, square));
+    drop(vnc);
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn desktop_resize() {
+    let (addr, server, release) = serve(vec![frame(2, 1, gradient), frame(1, 3, gradient)], None);
+    let mut vnc = VncBackend::connect(addr, None).unwrap();
+    let first = display(&mut vnc);
+    assert_eq!((first.width, first.height), (2, 1));
+    assert_eq!(first.pixels, frame(2, 1, gradient).pixels);
+    drop(release);
+    wait_for(&mut vnc, &frame(1, 3, gradient));
+    drop(vnc);
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn password() {
+    let (addr, server, _) = serve(vec![frame(2, 2, gradient)], Some("secret"));
+    let mut vnc = VncBackend::connect(addr, Some("secret")).unwrap();
+    assert_eq!(display(&mut vnc).pixels, frame(2, 2, gradient).pixels);
+    drop(vnc);
+    server.join().unwrap().unwrap();
+
+    let (addr, server, _) = serve(vec![frame(2, 2, gradient)], Some("secret"));
+    match VncBackend::connect(addr, Some("guess")) {
+        Err(Error::Vnc(e)) => assert_eq!(e.to_string(), "authentication failed"),
+        r => panic!("{:?}", r.map(|_| ())),
+    }
+    match server.join().unwrap() {
+        Err(Error::Vnc(e)) => assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn raw_in_viewer_pixel_format() {
+    let (addr, server, _) = serve(
+        vec![frame(2, 1, |x, _| [[255, 0, 0], [0, 255, 0]][x as usize])],
+        None,
+    );
+    let mut s = TcpStream::connect(addr).unwrap();
+    let mut version = [0; 12];
+    s.read_exact(&mut version).unwrap();
+    s.write_all(rfb::VERSION).unwrap();
+    let mut security = [0; 2];
+    s.read_exact(&mut security).unwrap();
+    assert_eq!(security, [1, rfb::SECURITY_NONE]);
+    s.write_all(&[rfb::SECURITY_NONE]).unwrap();
+    let mut result = [0; 4];
+    s.read_exact(&mut result).unwrap();
+    assert_eq!(result, [0; 4]);
+
+    s.write_all(&[1]).unwrap();
+    let mut init = [0; 24];
+    s.read_exact(&mut init).unwrap();
+    assert_eq!(init[..4], [0, 2, 0, 1]);
+    let mut name = vec![0; u32::from_be_bytes([init[20], init[21], init[22], init[23]]) as usize];
+    s.read_exact(&mut name).unwrap();
+
+    // big endian RGB565, raw only, then the whole screen
+    let mut msg = vec![
+        0, 0, 0, 0, 16, 16, 1, 1, 0, 31, 0, 63, 0, 31, 11, 5, 0, 0, 0, 0,
+    ];
+    msg.extend_from_slice(&[2, 0, 0, 1, 0, 0, 0, 0]);
+    msg.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 2, 0, 1]);
+    s.write_all(&msg).unwrap();
+
+    let mut update = [0; 4 + 12 + 4];
+    s.read_exact(&mut update).unwrap();
+    assert_eq!(update[..4], [0, 0, 0, 1]);
+    assert_eq!(update[4..16], [0, 0, 0, 0, 0, 2, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(update[16..], [0xf8, 0x00, 0x07, 0xe0]);
+    drop(s);
+    server.join().unwrap().unwrap();
+}
+
+fn bgra(width: u32, height: u32) -> RgbBuf {
+    frame(width, height, gradient)
+        .convert(PixelFormat::Bgra8)
+        .unwrap()
+}
+
+fn touch(buf: &mut RgbBuf, x: u32, y: u32) {
+    let i = ((y * buf.width + x) * 4) as usize;
+    buf.pixels[i] ^= 0xff;
+}
+
+#[test]
+fn dirty_tiles() {
+    let shown = bgra(200, 100);
+    let all = Region::new(0, 0, 200, 100);
+    assert_eq!(dirty(&shown, &bgra(200, 100), all), []);
+
+    let mut frame = bgra(200, 100);
+    touch(&mut frame, 70, 5);
+    assert_eq!(dirty(&shown, &frame, all), [Region::new(64, 0, 64, 64)]);
+    // outside the requested area
+    assert_eq!(dirty(&shown, &frame, Region::new(0, 0, 64, 64)), []);
+
+    // neighbours on a tile row merge, partial edge tiles are clipped
+    touch(&mut frame, 130, 5);
+    touch(&mut frame, 199, 99);
+    assert_eq!(
+        dirty(&shown, &frame, all),
+        [Region::new(64, 0, 128, 64), Region::new(192, 64, 8, 36)]
+    );
+}
+
+#[test]
+fn zrle_round_trip() {
+    // solid, palette RLE stripes, packed palette, plain RLE runs and a raw edge column
+    let source = frame(130, 80, |x, y| match (x / 64, y / 64) {
+        (0, 0) => [10, 20, 30],
+        (1, 0) if y % 2 == 0 => [255, 0, 0],
+        (1, 0) => [0, 0, 255],
+        (0, 1) => [[1, 2, 3], [4, 5, 6], [7, 8, 9], [9, 9, 9], [0, 0, 0]][(x / 3 % 5) as usize],
+        (1, 1) => gradient(x / 8, y),
+        _ => [(x * y) as u8, x as u8, y as u8],
+    })
+    .convert(PixelFormat::Bgra8)
+    .unwrap();
+    let format = ClientFormat::parse(&rfb::PIXEL_FORMAT).unwrap();
+    let mut deflate = Compress::new(Compression::default(), true);
+    let mut inflate = Decompress::new(true);
+    let mut decoded = RgbBuf::new(vec![0; 130 * 80 * 4], 130, 80, PixelFormat::Bgra8).unwrap();
+
+    // twice over the same zlib streams, first the whole frame then a part of it
+    for rect in [Region::new(0, 0, 130, 80), Region::new(30, 10, 90, 65)] {
+        let data = rfb::zrle_encode(&source, rect, &format, &mut deflate).unwrap();
+        let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        assert_eq!(len, data.len() - 4);
+        let mut tiles = Vec::with_capacity(130 * 80 * 4);
+        inflate
+            .decompress_vec(&data[4..], &mut tiles, FlushDecompress::Sync)
+            .unwrap();
+        rfb::zrle_decode(&tiles, &mut decoded, rect).unwrap();
+    }
+    let opaque: Vec<u8> = source
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect();
+    assert_eq!(decoded.pixels, opaque);
+}
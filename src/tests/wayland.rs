@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread::sleep;
+use std::time::Duration;
+use std::{env, fs};
+
+use wayland_client::protocol::wl_output::Transform;
+use wayland_client::protocol::wl_shm::Format;
+
+use crate::prelude::*;
+use crate::wayland::{logical_size, unpack, untransform};
+
+#[test]
+fn wayland_unpack_formats() {
+    // 1x2, stride padded to 8 bytes
+    let data = vec![1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+    let b = unpack(Format::Xrgb8888, data.clone(), 1, 2, 8).unwrap();
+    assert_eq!(b.format, PixelFormat::Bgra8);
+    assert_eq!(b.pixels, [1, 2, 3, 4, 5, 6, 7, 8]);
+    let b = unpack(Format::Abgr8888, data, 1, 2, 8).unwrap();
+    assert_eq!(b.format, PixelFormat::Rgba8);
+
+    let b = unpack(Format::Rgb565, vec![0x00, 0xf8], 1, 1, 2).unwrap();
+    assert_eq!(b.pixels, [0, 0, 255, 255]);
+
+    assert!(matches!(
+        unpack(Format::Yuyv, vec![0; 4], 1, 1, 4),
+        Err(Error::ScreencopyFailed)
+    ));
+}
+
+#[test]
+fn wayland_output_transforms() {
+    assert_eq!(logical_size(1920, 1080, Transform::Normal, 1), (1920, 1080));
+    assert_eq!(logical_size(1920, 1080, Transform::_90, 2), (540, 960));
+    assert_eq!(
+        logical_size(1920, 1080, Transform::Flipped180, 0),
+        (1920, 1080)
+    );
+
+    // a 1x2 output turned counter-clockwise by the compositor comes out 2x1
+    let buf = || RgbBuf::new(vec![1, 2], 2, 1, PixelFormat::Gray8).unwrap();
+    let b = untransform(buf(), Transform::_90);
+    assert_eq!((b.width, b.height, &b.pixels[..]), (1, 2, &[1, 2][..]));
+    let b = untransform(buf(), Transform::_270);
+    assert_eq!((b.width, b.height, &b.pixels[..]), (1, 2, &[2, 1][..]));
+    assert_eq!(untransform(buf(), Transform::Flipped).pixels, [2, 1]);
+    assert_eq!(untransform(buf(), Transform::Normal).pixels, [1, 2]);
+}
+
+// sway on the headless wlroots backend with the pixman renderer, killed on drop
+struct Sway {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Sway {
+    fn start() -> Sway {
+        let dir = env::temp_dir().join(format!("win-screenshot-sway-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        fs::write(
+            &config,
+            "output HEADLESS-1 resolution 320x240 position 0 0\n\
+             output HEADLESS-2 resolution 160x120 position 320 0 scale 2\n",
+        )
+        .unwrap();
+        let sway = Sway {
+            child: Command::new("sway")
+                .args(["--config".as_ref(), config.as_os_str()])
+                .env("XDG_RUNTIME_DIR", &dir)
+                .env("WLR_BACKENDS", "headless")
+                .env("WLR_RENDERER", "pixman")
+                .env("WLR_HEADLESS_OUTPUTS", "2")
+                .env("WLR_LIBINPUT_NO_DEVICES", "1")
+                .env_remove("WAYLAND_DISPLAY")
+                .env_remove("DISPLAY")
+                .spawn()
+                .expect("sway is not installed"),
+            dir,
+        };
+        sleep(Duration::from_millis(500));
+        sway
+    }
+
+    fn backend(&self) -> WaylandBackend {
+        for _ in 0..100 {
+            let socket = fs::read_dir(&self.dir).unwrap().flatten().find(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                name.starts_with("wayland-") && !name.ends_with(".lock")
+            });
+            if let Some(Ok(b)) = socket.map(|s| WaylandBackend::connect_to(s.path())) {
+                return b;
+            }
+            sleep(Duration::from_millis(50));
+        }
+        panic!("sway did not come up");
+    }
+}
+
+impl Drop for Sway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+#[ignore = "needs sway"]
+fn wayland_screencopy() {
+    let sway = Sway::start();
+    let mut backend = sway.backend();
+
+    let monitors = backend.targets().unwrap();
+    let names: Vec<_> = monitors.iter().map(|m| m.window_name.as_str()).collect();
+    assert_eq!(names, ["HEADLESS-1", "HEADLESS-2"]);
+
+    let b = CaptureRequest::window(monitors[1].hwnd)
+        .capture_with(&mut backend)
+        .unwrap();
+    assert_eq!((b.width, b.height), (160, 120));
+
+    // both outputs side by side in compositor space, the second one at half size
+    let b = CaptureRequest::display()
+        .capture_with(&mut backend)
+        .unwrap();
+    assert_eq!((b.width, b.height), (400, 240));
+
+    let b = CaptureRequest::display()
+        .region(Region::new(300, 100, 40, 10))
+        .capture_with(&mut backend)
+        .unwrap();
+    assert_eq!((b.width, b.height), (40, 10));
+}
@@ -0,0 +1,328 @@
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread::sleep;
+use std::time::Duration;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    AtomEnum, ConnectionExt, CreateWindowAux, PropMode, Window, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::prelude::*;
+
+static DISPLAY: AtomicU32 = AtomicU32::new(71);
+
+// Xvfb server on a free display, killed on drop
+pub(crate) struct Xvfb {
+    child: Child,
+    pub display: String,
+}
+
+impl Xvfb {
+    pub fn start() -> Xvfb {
+        let display = format!(":{}", DISPLAY.fetch_add(1, Ordering::SeqCst));
+        let x = Xvfb {
+            child: Command::new("Xvfb")
+                .args([&display, "-screen", "0", "320x240x24", "-nolisten", "tcp"])
+                .spawn()
+                .expect("Xvfb is not installed"),
+            display,
+        };
+        for _ in 0..100 {
+            if x11rb::connect(Some(&x.display)).is_ok() {
+                return x;
+            }
+            sleep(Duration::from_millis(50));
+        }
+        panic!("Xvfb {} did not come up", x.display);
+    }
+
+    pub fn connect(&self) -> RustConnection {
+        x11rb::connect(Some(&self.display)).unwrap().0
+    }
+
+    pub fn backend(&self) -> X11Backend {
+        X11Backend::connect_to(Some(&self.display)).unwrap()
+    }
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// mapped unmanaged window filled with a 0xRRGGBB color
+pub(crate) fn spawn_window(
+    conn: &RustConnection,
+    name: &str,
+    region: Region,
+    color: u32,
+) -> Window {
+    let root = conn.setup().roots[0].root;
+    let w = conn.generate_id().unwrap();
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        w,
+        root,
+        region.x as i16,
+        region.y as i16,
+        region.width as u16,
+        region.height as u16,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        0,
+        &CreateWindowAux::new()
+            .background_pixel(color)
+            .override_redirect(1),
+    )
+    .unwrap();
+    if !name.is_empty() {
+        conn.change_property8(
+            PropMode::REPLACE,
+            w,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            name.as_bytes(),
+        )
+        .unwrap();
+    }
+    conn.map_window(w).unwrap();
+    conn.sync().unwrap();
+    w
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_display() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    spawn_window(&conn, "red", Region::new(10, 20, 30, 40), 0xff0000);
+
+    let b = CaptureRequest::display()
+        .capture_with(&mut x.backend())
+        .unwrap();
+    assert_eq!(
+        (b.width, b.height, b.format),
+        (320, 240, PixelFormat::Rgba8)
+    );
+    let px = |x: usize, y: usize| &b.pixels[(y * 320 + x) * 4..][..4];
+    assert_eq!(px(15, 25), [255, 0, 0, 255]);
+    assert_eq!(px(5, 5), [0, 0, 0, 255]);
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_window_and_crop() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    let w = spawn_window(&conn, "green", Region::new(50, 50, 64, 32), 0x00ff00);
+
+    let b = CaptureRequest::window(w as isize)
+        .format(PixelFormat::Rgb8)
+        .capture_with(&mut x.backend())
+        .unwrap();
+    assert_eq!((b.width, b.height), (64, 32));
+    assert!(b.pixels.chunks_exact(3).all(|px| px == [0, 255, 0]));
+
+    let b = CaptureRequest::window(w as isize)
+        .region(Region::new(60, 0, 8, 8))
+        .region_policy(RegionPolicy::Clamp)
+        .capture_with(&mut x.backend())
+        .unwrap();
+    assert_eq!((b.width, b.height), (4, 8));
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_window_list() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    let a = spawn_window(&conn, "first", Region::new(0, 0, 10, 10), 0);
+    spawn_window(&conn, "", Region::new(0, 0, 10, 10), 0);
+    let hidden = spawn_window(&conn, "hidden", Region::new(0, 0, 10, 10), 0);
+    conn.unmap_window(hidden).unwrap();
+    conn.sync().unwrap();
+
+    let backend = x.backend();
+    let names: Vec<_> = backend
+        .window_list()
+        .unwrap()
+        .into_iter()
+        .map(|w| w.window_name)
+        .collect();
+    assert_eq!(names, ["first"]);
+    assert_eq!(backend.find_window("first").unwrap(), a as isize);
+    assert!(matches!(
+        backend.find_window("hidden"),
+        Err(Error::FindWindow(_))
+    ));
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_shm_matches_xgetimage() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    spawn_window(&conn, "blue", Region::new(5, 5, 100, 50), 0x0000ff);
+
+    let mut fast = x.backend();
+    assert!(fast.uses_shm());
+    let mut slow = x.backend();
+    slow.set_shm(false);
+    assert!(!slow.uses_shm());
+
+    for request in [
+        CaptureRequest::display(),
+        CaptureRequest::display().region(Region::new(3, 4, 17, 9)),
+    ] {
+        let a = request.capture_with(&mut fast).unwrap();
+        let b = request.capture_with(&mut slow).unwrap();
+        assert_eq!((a.width, a.height), (b.width, b.height));
+        assert_eq!(a.pixels, b.pixels);
+    }
+    assert!(fast.uses_shm());
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_capturer_grows_segment() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    let w = spawn_window(&conn, "red", Region::new(0, 0, 20, 20), 0xff0000);
+
+    let mut small = X11Capturer::with_backend(
+        x.backend(),
+        CaptureRequest::window(w as isize).format(PixelFormat::Rgb8),
+    );
+    for _ in 0..3 {
+        let b = small.capture().unwrap();
+        assert_eq!((b.width, b.height), (20, 20));
+        assert!(b.pixels.chunks_exact(3).all(|px| px == [255, 0, 0]));
+    }
+
+    // same backend, larger frame than the segment was made for
+    let b = CaptureRequest::display()
+        .capture_with(small.backend())
+        .unwrap();
+    assert_eq!((b.width, b.height), (320, 240));
+    assert!(small.backend().uses_shm());
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_composite_occluded() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    let under = spawn_window(&conn, "under", Region::new(10, 10, 40, 40), 0xff0000);
+    spawn_window(&conn, "over", Region::new(0, 0, 100, 100), 0x00ff00);
+
+    let request = CaptureRequest::window(under as isize).format(PixelFormat::Rgb8);
+    let mut capturer = X11Capturer::with_backend(x.backend(), request.using(Using::Composite));
+    let b = capturer.capture().unwrap();
+    assert_eq!((b.width, b.height), (40, 40));
+    assert!(b.pixels.chunks_exact(3).all(|px| px == [255, 0, 0]));
+
+    // plain XGetImage reads what is on screen
+    let b = request
+        .using(Using::BitBlt)
+        .capture_with(&mut x.backend())
+        .unwrap();
+    assert!(b.pixels.chunks_exact(3).all(|px| px == [0, 255, 0]));
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_composite_off_screen() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    // screen is 320x240, half of the window hangs off the right edge
+    let w = spawn_window(&conn, "edge", Region::new(300, 10, 40, 20), 0x0000ff);
+
+    let mut backend = x.backend();
+    backend.set_using(Using::Composite);
+    let request = CaptureRequest::window(w as isize).format(PixelFormat::Rgb8);
+    let b = request.capture_with(&mut backend).unwrap();
+    assert_eq!((b.width, b.height), (40, 20));
+    assert!(b.pixels.chunks_exact(3).all(|px| px == [0, 0, 255]));
+
+    assert!(request
+        .using(Using::BitBlt)
+        .capture_with(&mut x.backend())
+        .is_err());
+}
+
+fn atom(conn: &RustConnection, name: &str) -> u32 {
+    conn.intern_atom(false, name.as_bytes())
+        .unwrap()
+        .reply()
+        .unwrap()
+        .atom
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_ewmh_window_list() {
+    let x = Xvfb::start();
+    let conn = x.connect();
+    let r = Region::new(0, 0, 10, 10);
+    let utf8 = spawn_window(&conn, "", r, 0);
+    let latin1 = spawn_window(&conn, "", r, 0);
+    let hidden = spawn_window(&conn, "hidden", r, 0);
+    let untitled = spawn_window(&conn, "", r, 0);
+    // mapped and titled, but not managed
+    spawn_window(&conn, "unmanaged", r, 0);
+
+    conn.change_property8(
+        PropMode::REPLACE,
+        utf8,
+        atom(&conn, "_NET_WM_NAME"),
+        atom(&conn, "UTF8_STRING"),
+        "Ωmega".as_bytes(),
+    )
+    .unwrap();
+    conn.change_property8(
+        PropMode::REPLACE,
+        latin1,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        b"caf\xe9",
+    )
+    .unwrap();
+    conn.change_property32(
+        PropMode::REPLACE,
+        hidden,
+        atom(&conn, "_NET_WM_STATE"),
+        AtomEnum::ATOM,
+        &[atom(&conn, "_NET_WM_STATE_HIDDEN")],
+    )
+    .unwrap();
+    // stand in for the window manager
+    conn.change_property32(
+        PropMode::REPLACE,
+        conn.setup().roots[0].root,
+        atom(&conn, "_NET_CLIENT_LIST"),
+        AtomEnum::WINDOW,
+        &[utf8, latin1, hidden, untitled],
+    )
+    .unwrap();
+    conn.sync().unwrap();
+
+    let list: Vec<_> = x
+        .backend()
+        .window_list()
+        .unwrap()
+        .into_iter()
+        .map(|w| (w.hwnd, w.window_name))
+        .collect();
+    assert_eq!(
+        list,
+        [
+            (utf8 as isize, "Ωmega".to_string()),
+            (latin1 as isize, "café".to_string())
+        ]
+    );
+}
@@ -0,0 +1,86 @@
+use crate::prelude::*;
+
+const MODES: [(ColorMatrix, ColorRange); 4] = [
+    (ColorMatrix::Bt601, ColorRange::Full),
+    (ColorMatrix::Bt601, ColorRange::Limited),
+    (ColorMatrix::Bt709, ColorRange::Full),
+    (ColorMatrix::Bt709, ColorRange::Limited),
+];
+
+// odd sized, colour only changes between 2x2 blocks so chroma subsampling is lossless
+fn blocks(width: u32, height: u32) -> RgbBuf {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let (bx, by) = (x / 2, y / 2);
+            pixels.extend([(bx * 70) as u8, (by * 90) as u8, (bx * by * 40) as u8]);
+        }
+    }
+    RgbBuf::new(pixels, width, height, PixelFormat::Rgb8).unwrap()
+}
+
+#[test]
+fn yuv_plane_sizes() {
+    assert_eq!(YuvFrame::chroma_size(5, 3), (3, 2));
+    assert_eq!(YuvFrame::len_for(5, 3), 15 + 2 * 6);
+    let b = blocks(5, 3);
+    let f = YuvFrame::from_rgb(&b, YuvLayout::I420, ColorMatrix::Bt601, ColorRange::Full).unwrap();
+    let sizes: Vec<_> = f.planes().iter().map(|p| p.len()).collect();
+    assert_eq!(sizes, vec![15, 6, 6]);
+    let f = YuvFrame::from_rgb(&b, YuvLayout::Nv12, ColorMatrix::Bt601, ColorRange::Full).unwrap();
+    let sizes: Vec<_> = f.planes().iter().map(|p| p.len()).collect();
+    assert_eq!(sizes, vec![15, 12]);
+}
+
+#[test]
+fn yuv_known_values() {
+    let b = RgbBuf::new(vec![255, 255, 255, 0, 0, 0], 2, 1, PixelFormat::Rgb8).unwrap();
+    for (matrix, range) in MODES {
+        let f = YuvFrame::from_rgb(&b, YuvLayout::I420, matrix, range).unwrap();
+        let luma = match range {
+            ColorRange::Full => [255, 0],
+            ColorRange::Limited => [235, 16],
+        };
+        assert_eq!(f.data, vec![luma[0], luma[1], 128, 128]);
+    }
+}
+
+#[test]
+fn yuv_round_trip() {
+    for (w, h) in [(7, 5), (6, 4), (1, 1), (1, 3)] {
+        let b = blocks(w, h);
+        for layout in [YuvLayout::I420, YuvLayout::Nv12] {
+            for (matrix, range) in MODES {
+                let f = YuvFrame::from_rgb(&b, layout, matrix, range).unwrap();
+                let back = f.to_rgb(PixelFormat::Rgb8).unwrap();
+                assert_eq!((back.width, back.height), (w, h));
+                let err = b
+                    .pixels
+                    .iter()
+                    .zip(&back.pixels)
+                    .map(|(&a, &b)| (a as i32 - b as i32).abs())
+                    .max()
+                    .unwrap();
+                assert!(
+                    err <= 2,
+                    "{:?} {:?} {:?} {}x{}: {}",
+                    layout,
+                    matrix,
+                    range,
+                    w,
+                    h,
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn yuv_checks_size() {
+    let b = blocks(3, 3);
+    let mut f =
+        YuvFrame::from_rgb(&b, YuvLayout::Nv12, ColorMatrix::Bt709, ColorRange::Limited).unwrap();
+    f.data.pop();
+    assert!(f.to_rgb(PixelFormat::Bgra8).is_err());
+}
@@ -0,0 +1,63 @@
+use crate::buf::RgbBuf;
+
+// pixels.len() must match width, height and format, as RgbBuf::new makes sure;
+// keeping hand-set fields consistent is up to the caller
+impl RgbBuf {
+    pub fn flip_horizontal(mut self) -> RgbBuf {
+        debug_assert!(self.check().is_ok());
+        let c = self.format.bytes_per_pixel();
+        let row = self.width as usize * c;
+        if row > 0 {
+            for line in self.pixels.chunks_exact_mut(row) {
+                let w = line.len() / c;
+                for x in 0..w / 2 {
+                    let (l, r) = line.split_at_mut((w - 1 - x) * c);
+                    l[x * c..x * c + c].swap_with_slice(&mut r[..c]);
+                }
+            }
+        }
+        self
+    }
+
+    pub fn flip_vertical(mut self) -> RgbBuf {
+        debug_assert!(self.check().is_ok());
+        let row = self.width as usize * self.format.bytes_per_pixel();
+        let h = self.height as usize;
+        for y in 0..h / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((h - 1 - y) * row);
+            top[y * row..y * row + row].swap_with_slice(&mut bottom[..row]);
+        }
+        self
+    }
+
+    pub fn rotate90(self) -> RgbBuf {
+        self.transpose().flip_horizontal()
+    }
+
+    pub fn rotate180(self) -> RgbBuf {
+        self.flip_horizontal().flip_vertical()
+    }
+
+    pub fn rotate270(self) -> RgbBuf {
+        self.transpose().flip_vertical()
+    }
+
+    fn transpose(self) -> RgbBuf {
+        debug_assert!(self.check().is_ok());
+        let c = self.format.bytes_per_pixel();
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut pixels = vec![0; self.pixels.len()];
+        for y in 0..h {
+            for x in 0..w {
+                let (s, d) = ((y * w + x) * c, (x * h + y) * c);
+                pixels[d..d + c].copy_from_slice(&self.pixels[s..s + c]);
+            }
+        }
+        RgbBuf {
+            pixels,
+            width: self.height,
+            height: self.width,
+            format: self.format,
+        }
+    }
+}
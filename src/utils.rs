@@ -1,11 +1,7 @@
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStrExt;
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::{HWND, LPARAM};
-use windows::core::BOOL;
-use windows::Win32::UI::WindowsAndMessaging::{
-    EnumWindows, FindWindowW, GetWindowTextLengthW, GetWindowTextW, IsWindowVisible,
-};
+#[cfg(not(windows))]
+use crate::error::Error;
+#[cfg(all(unix, feature = "x11"))]
+use crate::x11::X11Backend;
 
 #[derive(Debug)]
 pub struct HwndName {
@@ -13,77 +9,105 @@ pub struct HwndName {
     pub window_name: String,
 }
 
-#[derive(Debug)]
-pub enum FWError {
-    NotFoundOrFault,
+#[cfg(windows)]
+pub use win::{find_window, window_list};
+
+#[cfg(all(unix, feature = "x11"))]
+pub fn find_window(window_name: &str) -> Result<isize, Error> {
+    X11Backend::connect()?.find_window(window_name)
 }
 
-pub fn find_window(window_name: &str) -> Result<isize, FWError> {
-    unsafe {
-        let w = FindWindowW(
-            PCWSTR::null(),
-            PCWSTR(
-                OsString::from(window_name)
-                    .encode_wide()
-                    .chain(Some(0))
-                    .collect::<Vec<_>>()
-                    .as_ptr(),
-            ),
-        );
-        match w {
-            Err(_) => Err(FWError::NotFoundOrFault),
-            Ok(HWND(p)) => Ok(p as isize),
-        }
-    }
+#[cfg(all(unix, feature = "x11"))]
+pub fn window_list() -> Result<Vec<HwndName>, Error> {
+    X11Backend::connect()?.window_list()
 }
 
-unsafe extern "system" fn wl_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
-    let vec = lparam.0 as *mut Vec<HwndName>;
+#[cfg(not(any(windows, all(unix, feature = "x11"))))]
+pub fn find_window(_window_name: &str) -> Result<isize, Error> {
+    Err(Error::Unsupported)
+}
 
-    if IsWindowVisible(hwnd) == false {
-        return BOOL::from(true);
-    }
+#[cfg(not(any(windows, all(unix, feature = "x11"))))]
+pub fn window_list() -> Result<Vec<HwndName>, Error> {
+    Err(Error::Unsupported)
+}
 
-    let gwtl = GetWindowTextLengthW(hwnd);
-    if gwtl == 0 {
-        return BOOL::from(true);
-    }
+#[cfg(windows)]
+mod win {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::BOOL;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{HWND, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, FindWindowW, GetWindowTextLengthW, GetWindowTextW, IsWindowVisible,
+    };
 
-    let mut name_buf: Vec<u16> = vec![0; (gwtl + 1) as usize];
+    use super::HwndName;
+    use crate::error::Error;
 
-    let gwt = GetWindowTextW(hwnd, &mut name_buf);
-    if gwt == 0 {
-        return BOOL::from(true);
+    pub fn find_window(window_name: &str) -> Result<isize, Error> {
+        unsafe {
+            let w = FindWindowW(
+                PCWSTR::null(),
+                PCWSTR(
+                    OsString::from(window_name)
+                        .encode_wide()
+                        .chain(Some(0))
+                        .collect::<Vec<_>>()
+                        .as_ptr(),
+                ),
+            );
+            match w {
+                Err(e) => Err(Error::FindWindow(e)),
+                Ok(HWND(p)) => Ok(p as isize),
+            }
+        }
     }
 
-    let name_buf = match name_buf.split_last() {
-        Some((_, last)) => last,
-        None => return BOOL::from(true),
-    };
+    unsafe extern "system" fn wl_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let vec = lparam.0 as *mut Vec<HwndName>;
 
-    let name = String::from_utf16_lossy(name_buf);
+        if IsWindowVisible(hwnd) == false {
+            return BOOL::from(true);
+        }
 
-    (*vec).push(HwndName {
-        hwnd: hwnd.0 as isize,
-        window_name: name,
-    });
+        let gwtl = GetWindowTextLengthW(hwnd);
+        if gwtl == 0 {
+            return BOOL::from(true);
+        }
 
-    BOOL::from(true)
-}
+        let mut name_buf: Vec<u16> = vec![0; (gwtl + 1) as usize];
 
-#[derive(Debug)]
-pub enum WLError {
-    EnumWindowsError,
-}
+        let gwt = GetWindowTextW(hwnd, &mut name_buf);
+        if gwt == 0 {
+            return BOOL::from(true);
+        }
+
+        let name_buf = match name_buf.split_last() {
+            Some((_, last)) => last,
+            None => return BOOL::from(true),
+        };
 
-pub fn window_list() -> Result<Vec<HwndName>, WLError> {
-    let mut hwnd_name = Vec::new();
-    unsafe {
-        EnumWindows(
-            Some(wl_callback),
-            LPARAM(&mut hwnd_name as *mut Vec<HwndName> as isize),
-        )
-        .map_err(|_| WLError::EnumWindowsError)?
+        let name = String::from_utf16_lossy(name_buf);
+
+        (*vec).push(HwndName {
+            hwnd: hwnd.0 as isize,
+            window_name: name,
+        });
+
+        BOOL::from(true)
+    }
+
+    pub fn window_list() -> Result<Vec<HwndName>, Error> {
+        let mut hwnd_name = Vec::new();
+        unsafe {
+            EnumWindows(
+                Some(wl_callback),
+                LPARAM(&mut hwnd_name as *mut Vec<HwndName> as isize),
+            )
+            .map_err(Error::EnumWindows)?
+        }
+        Ok(hwnd_name)
     }
-    Ok(hwnd_name)
 }
@@ -0,0 +1,272 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use flate2::Decompress;
+
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::capture::Area;
+use crate::error::Error;
+use crate::region::Region;
+use crate::request::Target;
+use crate::rfb::{self, read_bytes, read_string, read_u16, read_u32, read_u8};
+use crate::utils::HwndName;
+
+// encodings we ask for, most preferred first
+const ENCODINGS: [i32; 4] = [rfb::ZRLE, rfb::COPY_RECT, rfb::RAW, rfb::DESKTOP_SIZE];
+
+// RFB 3.8 client keeping a local copy of the remote framebuffer, the whole
+// remote screen is Target::Display
+pub struct VncBackend {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    framebuffer: RgbBuf,
+    name: String,
+    // one zlib stream per connection, shared by all ZRLE rectangles
+    inflate: Decompress,
+}
+
+impl VncBackend {
+    // host:port of the server, password only for VNC authentication
+    pub fn connect<A: ToSocketAddrs>(addr: A, password: Option<&str>) -> Result<VncBackend, Error> {
+        VncBackend::handshake(addr, password).map_err(Error::Vnc)
+    }
+
+    fn handshake<A: ToSocketAddrs>(addr: A, password: Option<&str>) -> io::Result<VncBackend> {
+        let writer = TcpStream::connect(addr)?;
+        writer.set_nodelay(true)?;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        let r = &mut reader;
+        let mut w = &writer;
+
+        let mut version = [0; 12];
+        r.read_exact(&mut version)?;
+        if !version.starts_with(b"RFB 003.") || version[..] < rfb::VERSION[..] {
+            return Err(rfb::protocol_error("server does not speak RFB 3.8"));
+        }
+        w.write_all(rfb::VERSION)?;
+
+        let types = match read_u8(r)? {
+            0 => return Err(io::Error::other(read_string(r)?)),
+            n => read_bytes(r, n as usize)?,
+        };
+        let security = match password {
+            Some(_) if types.contains(&rfb::SECURITY_VNC) => rfb::SECURITY_VNC,
+            _ if types.contains(&rfb::SECURITY_NONE) => rfb::SECURITY_NONE,
+            _ if types.contains(&rfb::SECURITY_VNC) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "server requires a password",
+                ))
+            }
+            _ => return Err(rfb::protocol_error("no supported security type")),
+        };
+        w.write_all(&[security])?;
+        if security == rfb::SECURITY_VNC {
+            let mut challenge = [0; 16];
+            r.read_exact(&mut challenge)?;
+            w.write_all(&rfb::vnc_auth(password.unwrap_or_default(), &challenge))?;
+        }
+        if read_u32(r)? != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                read_string(r)?,
+            ));
+        }
+
+        // shared, other viewers stay connected
+        w.write_all(&[1])?;
+        let (width, height) = (read_u16(r)?, read_u16(r)?);
+        rfb::check_size(width, height)?;
+        read_bytes(r, 16)?;
+        let name = read_string(r)?;
+
+        let mut msg = vec![0, 0, 0, 0];
+        msg.extend_from_slice(&rfb::PIXEL_FORMAT);
+        msg.extend_from_slice(&[2, 0]);
+        msg.extend_from_slice(&(ENCODINGS.len() as u16).to_be_bytes());
+        for e in ENCODINGS {
+            msg.extend_from_slice(&e.to_be_bytes());
+        }
+        w.write_all(&msg)?;
+
+        let mut vnc = VncBackend {
+            reader,
+            writer,
+            framebuffer: blank(width, height),
+            name,
+            inflate: Decompress::new(true),
+        };
+        vnc.update(false)?;
+        Ok(vnc)
+    }
+
+    // desktop name from ServerInit
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // local copy as of the last processed update
+    pub fn framebuffer(&self) -> &RgbBuf {
+        &self.framebuffer
+    }
+
+    // ask for the whole screen and wait until it arrives
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        self.update(false).map_err(Error::Vnc)
+    }
+
+    // apply whatever the server already sent without waiting for more
+    pub fn poll(&mut self) -> Result<(), Error> {
+        self.drain().map_err(Error::Vnc)
+    }
+
+    // request an update, block until it is applied and keep an incremental one outstanding
+    fn update(&mut self, incremental: bool) -> io::Result<()> {
+        self.request(incremental)?;
+        while !self.message()? {}
+        self.request(true)
+    }
+
+    fn request(&mut self, incremental: bool) -> io::Result<()> {
+        let mut msg = vec![3, incremental as u8, 0, 0, 0, 0];
+        msg.extend_from_slice(&(self.framebuffer.width as u16).to_be_bytes());
+        msg.extend_from_slice(&(self.framebuffer.height as u16).to_be_bytes());
+        self.writer.write_all(&msg)
+    }
+
+    fn drain(&mut self) -> io::Result<()> {
+        while rfb::pending(&mut self.reader)? {
+            if self.message()? {
+                self.request(true)?;
+            }
+        }
+        Ok(())
+    }
+
+    // reads one server message, true if it was a framebuffer update
+    fn message(&mut self) -> io::Result<bool> {
+        let r = &mut self.reader;
+        match read_u8(r)? {
+            0 => {
+                read_u8(r)?;
+                for _ in 0..read_u16(r)? {
+                    self.rectangle()?;
+                }
+                Ok(true)
+            }
+            // SetColourMapEntries, unused with a true color format
+            1 => {
+                read_bytes(r, 3)?;
+                let n = read_u16(r)?;
+                read_bytes(r, n as usize * 6)?;
+                Ok(false)
+            }
+            // Bell
+            2 => Ok(false),
+            // ServerCutText
+            3 => {
+                read_bytes(r, 3)?;
+                let len = read_u32(r)?;
+                rfb::skip(r, len as u64)?;
+                Ok(false)
+            }
+            _ => Err(rfb::protocol_error("unknown server message")),
+        }
+    }
+
+    fn rectangle(&mut self) -> io::Result<()> {
+        let r = &mut self.reader;
+        let rect = Region::new(
+            read_u16(r)? as i32,
+            read_u16(r)? as i32,
+            read_u16(r)? as i32,
+            read_u16(r)? as i32,
+        );
+        let encoding = read_u32(r)? as i32;
+        if encoding == rfb::DESKTOP_SIZE {
+            rfb::check_size(rect.width as u16, rect.height as u16)?;
+            self.framebuffer = blank(rect.width as u16, rect.height as u16);
+            return Ok(());
+        }
+        let fb = Region::new(
+            0,
+            0,
+            self.framebuffer.width as i32,
+            self.framebuffer.height as i32,
+        );
+        if rect.intersect(&fb) != Some(rect) {
+            return Err(rfb::protocol_error("rectangle outside the framebuffer"));
+        }
+        match encoding {
+            rfb::RAW => {
+                let data = read_bytes(r, rect.width as usize * rect.height as usize * 4)?;
+                let pixels = data
+                    .chunks_exact(4)
+                    .map(|p| [p[0], p[1], p[2], 255])
+                    .collect::<Vec<_>>();
+                rfb::paint(&mut self.framebuffer, rect, &pixels);
+            }
+            rfb::COPY_RECT => {
+                let src = Region::new(
+                    read_u16(r)? as i32,
+                    read_u16(r)? as i32,
+                    rect.width,
+                    rect.height,
+                );
+                let pixels = rfb::pixels(&self.framebuffer, src).ok_or_else(|| {
+                    rfb::protocol_error("CopyRect source outside the framebuffer")
+                })?;
+                rfb::paint(&mut self.framebuffer, rect, &pixels);
+            }
+            rfb::ZRLE => {
+                let limit = rfb::zrle_max_len(rect.width as usize, rect.height as usize);
+                let len = read_u32(r)? as usize;
+                // stored deflate blocks grow by 5 bytes in 64k
+                if len > limit + limit / 1024 + 64 {
+                    return Err(rfb::protocol_error("ZRLE data larger than its rectangle"));
+                }
+                let data = read_bytes(r, len)?;
+                let data = rfb::inflate(&mut self.inflate, &data, limit)?;
+                rfb::zrle_decode(&data, &mut self.framebuffer, rect)?;
+            }
+            _ => return Err(rfb::protocol_error("encoding was not requested")),
+        }
+        Ok(())
+    }
+}
+
+fn blank(width: u16, height: u16) -> RgbBuf {
+    let len = RgbBuf::len_for(width as u32, height as u32, PixelFormat::Bgra8);
+    RgbBuf {
+        pixels: vec![0; len],
+        width: width as u32,
+        height: height as u32,
+        format: PixelFormat::Bgra8,
+    }
+}
+
+impl CaptureBackend for VncBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn geometry(&mut self, target: Target, _area: Area) -> Result<Geometry, Error> {
+        if target != Target::Display {
+            return Err(Error::TargetNotFound(target));
+        }
+        self.poll()?;
+        Ok(Geometry {
+            width: self.framebuffer.width as i32,
+            height: self.framebuffer.height as i32,
+            mirrored: false,
+        })
+    }
+
+    fn grab(&mut self, target: Target, _area: Area, src: Region) -> Result<RgbBuf, Error> {
+        if target != Target::Display {
+            return Err(Error::TargetNotFound(target));
+        }
+        Ok(self.framebuffer.view(src)?.to_owned())
+    }
+}
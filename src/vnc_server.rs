@@ -0,0 +1,378 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+use flate2::{Compress, Compression};
+
+use crate::backend::CaptureBackend;
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::error::Error;
+use crate::region::Region;
+use crate::request::CaptureRequest;
+use crate::rfb::{self, read_bytes, read_u16, read_u32, read_u8, ClientFormat};
+
+const TILE: i32 = 64;
+
+// RFB 3.8 server showing the frames of a CaptureRequest to VNC viewers, one at a time
+pub struct VncServer {
+    listener: TcpListener,
+    password: Option<String>,
+    name: String,
+    interval: Duration,
+}
+
+impl VncServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<VncServer, Error> {
+        Ok(VncServer {
+            listener: TcpListener::bind(addr).map_err(Error::Vnc)?,
+            password: None,
+            name: "win-screenshot".to_string(),
+            interval: Duration::from_millis(50),
+        })
+    }
+
+    // VNC authentication, only the first 8 characters count
+    pub fn password(mut self, password: &str) -> VncServer {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    // desktop name viewers show in their title
+    pub fn name(mut self, name: &str) -> VncServer {
+        self.name = name.to_string();
+        self
+    }
+
+    // shortest time between two captures
+    pub fn interval(mut self, interval: Duration) -> VncServer {
+        self.interval = interval;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr().map_err(Error::Vnc)
+    }
+
+    // serve viewers one after another until capturing fails
+    pub fn run<B: CaptureBackend>(&self, mut backend: B, request: CaptureRequest) -> Error {
+        loop {
+            match self.serve(&mut backend, request) {
+                Ok(()) | Err(Error::Vnc(_)) => continue,
+                Err(e) => return e,
+            }
+        }
+    }
+
+    // accept one viewer and serve it until it disconnects
+    pub fn serve<B: CaptureBackend>(
+        &self,
+        mut backend: B,
+        request: CaptureRequest,
+    ) -> Result<(), Error> {
+        let (stream, _) = self.listener.accept().map_err(Error::Vnc)?;
+        let mut viewer = Viewer::handshake(stream, self.password.as_deref()).map_err(Error::Vnc)?;
+
+        let mut frame = Some(capture(&mut backend, request)?);
+        let size = frame.as_ref().map_or((0, 0), |f| (f.width, f.height));
+        viewer.init(size, &self.name).map_err(Error::Vnc)?;
+
+        let mut next = Instant::now();
+        loop {
+            // nothing asked for yet, wait for the viewer
+            if viewer.wanted.is_none() {
+                match viewer.message().map_err(Error::Vnc)? {
+                    true => continue,
+                    false => return Ok(()),
+                }
+            }
+            while rfb::pending(&mut viewer.reader).map_err(Error::Vnc)? {
+                if !viewer.message().map_err(Error::Vnc)? {
+                    return Ok(());
+                }
+            }
+
+            let frame = match frame.take() {
+                Some(f) => f,
+                None => {
+                    sleep(next.saturating_duration_since(Instant::now()));
+                    next = Instant::now() + self.interval;
+                    capture(&mut backend, request)?
+                }
+            };
+            viewer.update(frame).map_err(Error::Vnc)?;
+        }
+    }
+}
+
+fn capture<B: CaptureBackend>(backend: &mut B, request: CaptureRequest) -> Result<RgbBuf, Error> {
+    Ok(request.capture_with(backend)?.convert(PixelFormat::Bgra8)?)
+}
+
+// not cryptographic, neither is VNC authentication
+fn challenge() -> [u8; 16] {
+    let mut c = [0; 16];
+    for half in c.chunks_exact_mut(8) {
+        let mut h = RandomState::new().build_hasher();
+        if let Ok(t) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            h.write_u128(t.as_nanos());
+        }
+        half.copy_from_slice(&h.finish().to_ne_bytes());
+    }
+    c
+}
+
+struct Viewer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    format: ClientFormat,
+    encodings: Vec<i32>,
+    // one zlib stream per connection, shared by all ZRLE rectangles
+    deflate: Compress,
+    size: (u32, u32),
+    // what the viewer has on screen, None until the first full update
+    shown: Option<RgbBuf>,
+    // FramebufferUpdateRequest not answered yet
+    wanted: Option<(Region, bool)>,
+}
+
+impl Viewer {
+    fn handshake(writer: TcpStream, password: Option<&str>) -> io::Result<Viewer> {
+        writer.set_nodelay(true)?;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        let r = &mut reader;
+        let mut w = &writer;
+
+        w.write_all(rfb::VERSION)?;
+        let mut version = [0; 12];
+        r.read_exact(&mut version)?;
+        if !version.starts_with(b"RFB 003.") || version[..] < rfb::VERSION[..] {
+            w.write_all(&[0])?;
+            w.write_all(&string("RFB 3.8 is required"))?;
+            return Err(rfb::protocol_error("viewer does not speak RFB 3.8"));
+        }
+
+        let security = match password {
+            Some(_) => rfb::SECURITY_VNC,
+            None => rfb::SECURITY_NONE,
+        };
+        w.write_all(&[1, security])?;
+        if read_u8(r)? != security {
+            w.write_all(&1u32.to_be_bytes())?;
+            w.write_all(&string("security type not offered"))?;
+            return Err(rfb::protocol_error("viewer chose another security type"));
+        }
+        if let Some(password) = password {
+            let challenge = challenge();
+            w.write_all(&challenge)?;
+            let mut response = [0; 16];
+            r.read_exact(&mut response)?;
+            if response != rfb::vnc_auth(password, &challenge) {
+                w.write_all(&1u32.to_be_bytes())?;
+                w.write_all(&string("authentication failed"))?;
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "viewer sent a wrong password",
+                ));
+            }
+        }
+        w.write_all(&0u32.to_be_bytes())?;
+
+        // shared flag, there is only ever one viewer
+        read_u8(r)?;
+        Ok(Viewer {
+            reader,
+            writer,
+            format: ClientFormat::parse(&rfb::PIXEL_FORMAT)?,
+            encodings: vec![rfb::RAW],
+            deflate: Compress::new(Compression::default(), true),
+            size: (0, 0),
+            shown: None,
+            wanted: None,
+        })
+    }
+
+    fn init(&mut self, (width, height): (u32, u32), name: &str) -> io::Result<()> {
+        self.size = (width, height);
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&(width as u16).to_be_bytes());
+        msg.extend_from_slice(&(height as u16).to_be_bytes());
+        msg.extend_from_slice(&rfb::PIXEL_FORMAT);
+        msg.extend_from_slice(&string(name));
+        self.writer.write_all(&msg)
+    }
+
+    // reads one client message, false once the viewer hung up
+    fn message(&mut self) -> io::Result<bool> {
+        let r = &mut self.reader;
+        let mut kind = [0; 1];
+        if r.read(&mut kind)? == 0 {
+            return Ok(false);
+        }
+        match kind[0] {
+            // SetPixelFormat, whatever is on screen has to be sent again
+            0 => {
+                read_bytes(r, 3)?;
+                let mut raw = [0; 16];
+                r.read_exact(&mut raw)?;
+                self.format = ClientFormat::parse(&raw)?;
+                self.shown = None;
+            }
+            // SetEncodings
+            2 => {
+                read_u8(r)?;
+                let n = read_u16(r)?;
+                self.encodings = (0..n)
+                    .map(|_| read_u32(r).map(|e| e as i32))
+                    .collect::<io::Result<_>>()?;
+            }
+            // FramebufferUpdateRequest, merged with one still waiting
+            3 => {
+                let incremental = read_u8(r)? != 0;
+                let region = Region::new(
+                    read_u16(r)? as i32,
+                    read_u16(r)? as i32,
+                    read_u16(r)? as i32,
+                    read_u16(r)? as i32,
+                );
+                self.wanted = Some(match self.wanted {
+                    Some((old, i)) => (bounds(old, region), i && incremental),
+                    None => (region, incremental),
+                });
+            }
+            // KeyEvent, PointerEvent: view only
+            4 => drop(read_bytes(r, 7)?),
+            5 => drop(read_bytes(r, 5)?),
+            // ClientCutText
+            6 => {
+                read_bytes(r, 3)?;
+                let len = read_u32(r)?;
+                rfb::skip(r, len as u64)?;
+            }
+            _ => return Err(rfb::protocol_error("unknown client message")),
+        }
+        Ok(true)
+    }
+
+    // answers the waiting request with whatever changed, keeps waiting if nothing did
+    fn update(&mut self, frame: RgbBuf) -> io::Result<()> {
+        let Some((wanted, incremental)) = self.wanted else {
+            return Ok(());
+        };
+        let full = Region::new(0, 0, frame.width as i32, frame.height as i32);
+        let resized = (frame.width, frame.height) != self.size;
+        if resized && !self.encodings.contains(&rfb::DESKTOP_SIZE) {
+            return Err(io::Error::other(
+                "frame size changed and the viewer can't resize",
+            ));
+        }
+
+        let rects = match (&self.shown, wanted.intersect(&full)) {
+            (Some(shown), Some(area)) if !resized && incremental => dirty(shown, &frame, area),
+            (Some(_), Some(area)) if !resized => vec![area],
+            (Some(_), None) if !resized => Vec::new(),
+            // viewer's screen is unknown, send all of it
+            _ => vec![full],
+        };
+        if rects.is_empty() && incremental {
+            return Ok(());
+        }
+
+        let encoding = self
+            .encodings
+            .iter()
+            .copied()
+            .find(|&e| e == rfb::ZRLE || e == rfb::RAW)
+            .unwrap_or(rfb::RAW);
+        let mut msg = vec![0, 0];
+        msg.extend_from_slice(&((rects.len() + resized as usize) as u16).to_be_bytes());
+        if resized {
+            header(&mut msg, full, rfb::DESKTOP_SIZE);
+            self.size = (frame.width, frame.height);
+        }
+        for &rect in &rects {
+            header(&mut msg, rect, encoding);
+            match encoding {
+                rfb::ZRLE => msg.extend(rfb::zrle_encode(
+                    &frame,
+                    rect,
+                    &self.format,
+                    &mut self.deflate,
+                )?),
+                _ => {
+                    for row in frame.view(rect).map_err(io::Error::other)?.rows() {
+                        for px in row.chunks_exact(4) {
+                            self.format.put(px, &mut msg);
+                        }
+                    }
+                }
+            }
+        }
+        self.writer.write_all(&msg)?;
+        self.wanted = None;
+
+        match &mut self.shown {
+            Some(shown) if rects != [full] => {
+                for &rect in &rects {
+                    let pixels = rfb::pixels(&frame, rect).expect("rect inside frame");
+                    rfb::paint(shown, rect, &pixels);
+                }
+            }
+            _ => self.shown = Some(frame),
+        }
+        Ok(())
+    }
+}
+
+fn string(s: &str) -> Vec<u8> {
+    let mut b = (s.len() as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(s.as_bytes());
+    b
+}
+
+fn header(msg: &mut Vec<u8>, r: Region, encoding: i32) {
+    for v in [r.x, r.y, r.width, r.height] {
+        msg.extend_from_slice(&(v as u16).to_be_bytes());
+    }
+    msg.extend_from_slice(&encoding.to_be_bytes());
+}
+
+fn bounds(a: Region, b: Region) -> Region {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+    Region::new(x, y, right - x, bottom - y)
+}
+
+// 64x64 tiles of area that differ between the frames, runs of them on a tile row merged
+pub(crate) fn dirty(shown: &RgbBuf, frame: &RgbBuf, area: Region) -> Vec<Region> {
+    let mut rects: Vec<Region> = Vec::new();
+    for ty in (area.y..area.y + area.height).step_by(TILE as usize) {
+        let mut run: Option<Region> = None;
+        for tx in (area.x..area.x + area.width).step_by(TILE as usize) {
+            let tile = Region::new(
+                tx,
+                ty,
+                TILE.min(area.x + area.width - tx),
+                TILE.min(area.y + area.height - ty),
+            );
+            let changed = match (shown.view(tile), frame.view(tile)) {
+                (Ok(a), Ok(b)) => !a.rows().eq(b.rows()),
+                _ => true,
+            };
+            run = match (run, changed) {
+                (Some(r), true) => Some(Region::new(r.x, r.y, r.width + tile.width, r.height)),
+                (None, true) => Some(tile),
+                (Some(r), false) => {
+                    rects.push(r);
+                    None
+                }
+                (None, false) => None,
+            };
+        }
+        rects.extend(run);
+    }
+    rects
+}
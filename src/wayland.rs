@@ -0,0 +1,527 @@
+use std::fs::File;
+use std::os::fd::{AsFd, FromRawFd};
+use std::os::unix::fs::FileExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_output::{self, Transform, WlOutput};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_shm::{self, WlShm};
+use wayland_client::protocol::wl_shm_pool::WlShmPool;
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::{self, ZxdgOutputV1};
+use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_frame_v1::{
+    self, ZwlrScreencopyFrameV1,
+};
+use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1;
+
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::{PixelFormat, RgbBuf};
+use crate::capture::Area;
+use crate::error::Error;
+use crate::packed::Packed;
+use crate::region::Region;
+use crate::request::Target;
+use crate::resize::Filter;
+use crate::utils::HwndName;
+
+// wl_output as the compositor describes it
+#[derive(Debug, Clone)]
+struct Monitor {
+    // registry name, stands in for hwnd
+    global: u32,
+    output: WlOutput,
+    name: String,
+    // position in compositor space
+    x: i32,
+    y: i32,
+    // current mode, the size of screencopy buffers
+    width: i32,
+    height: i32,
+    transform: Transform,
+    scale: i32,
+    // from xdg-output, fractional scales leave wl_output guessing
+    logical_position: Option<(i32, i32)>,
+    logical_size: Option<(i32, i32)>,
+}
+
+impl Monitor {
+    // what the output covers of Target::Display
+    fn logical(&self) -> Region {
+        let (x, y) = self.logical_position.unwrap_or((self.x, self.y));
+        let (width, height) = self
+            .logical_size
+            .unwrap_or_else(|| logical_size(self.width, self.height, self.transform, self.scale));
+        Region::new(x, y, width, height)
+    }
+}
+
+// mode size turned like the output and divided by its scale
+pub(crate) fn logical_size(
+    width: i32,
+    height: i32,
+    transform: Transform,
+    scale: i32,
+) -> (i32, i32) {
+    let (w, h) = match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            (height, width)
+        }
+        _ => (width, height),
+    };
+    (w / scale.max(1), h / scale.max(1))
+}
+
+// undo what the compositor does to content for a rotated or mirrored output:
+// flip around the vertical axis, then turn counter-clockwise
+pub(crate) fn untransform(buf: RgbBuf, transform: Transform) -> RgbBuf {
+    let turned = match transform {
+        Transform::_90 | Transform::Flipped90 => buf.rotate90(),
+        Transform::_180 | Transform::Flipped180 => buf.rotate180(),
+        Transform::_270 | Transform::Flipped270 => buf.rotate270(),
+        _ => buf,
+    };
+    match transform {
+        Transform::Flipped
+        | Transform::Flipped90
+        | Transform::Flipped180
+        | Transform::Flipped270 => turned.flip_horizontal(),
+        _ => turned,
+    }
+}
+
+// what the compositor said about the frame being copied
+#[derive(Default)]
+struct Frame {
+    // format, width, height, stride of the wl_shm buffer to copy into,
+    // only ever a format unpack handles
+    buffer: Option<(wl_shm::Format, u32, u32, u32)>,
+    // any buffer was offered, usable or not
+    offered: bool,
+    buffer_done: bool,
+    y_invert: bool,
+    ready: bool,
+    failed: bool,
+}
+
+struct State {
+    monitors: Vec<Monitor>,
+    frame: Frame,
+}
+
+// wlroots screencopy over wl_shm buffers. Every wl_output is a target like a
+// window, Target::Display is all outputs laid out at their compositor positions
+pub struct WaylandBackend {
+    queue: EventQueue<State>,
+    state: State,
+    shm: WlShm,
+    manager: ZwlrScreencopyManagerV1,
+    // protocol version, buffer_done is only sent from 3 on
+    version: u32,
+}
+
+impl WaylandBackend {
+    // compositor from $WAYLAND_DISPLAY
+    pub fn connect() -> Result<WaylandBackend, Error> {
+        WaylandBackend::with_connection(Connection::connect_to_env()?)
+    }
+
+    // socket path like $XDG_RUNTIME_DIR/wayland-1
+    pub fn connect_to<P: AsRef<Path>>(socket: P) -> Result<WaylandBackend, Error> {
+        let stream = UnixStream::connect(socket).map_err(Error::WaylandIo)?;
+        WaylandBackend::with_connection(Connection::from_socket(stream)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<WaylandBackend, Error> {
+        let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+        let qh = queue.handle();
+        let shm: WlShm = globals.bind(&qh, 1..=1, ())?;
+        let manager: ZwlrScreencopyManagerV1 = globals.bind(&qh, 1..=3, ())?;
+        let version = manager.version();
+        // logical layout, sway and friends have it
+        let xdg: Option<ZxdgOutputManagerV1> = globals.bind(&qh, 1..=3, ()).ok();
+
+        let mut state = State {
+            monitors: Vec::new(),
+            frame: Frame::default(),
+        };
+        for g in globals.contents().clone_list() {
+            if g.interface == "wl_output" {
+                let output = globals
+                    .registry()
+                    .bind(g.name, g.version.min(4), &qh, g.name);
+                if let Some(xdg) = &xdg {
+                    xdg.get_xdg_output(&output, &qh, g.name);
+                }
+                state.monitors.push(Monitor {
+                    global: g.name,
+                    output,
+                    name: format!("output-{}", g.name),
+                    x: 0,
+                    y: 0,
+                    width: 0,
+                    height: 0,
+                    transform: Transform::Normal,
+                    scale: 1,
+                    logical_position: None,
+                    logical_size: None,
+                });
+            }
+        }
+        // geometry, mode, scale, name and xdg-output events
+        queue.roundtrip(&mut state)?;
+        Ok(WaylandBackend {
+            queue,
+            state,
+            shm,
+            manager,
+            version,
+        })
+    }
+
+    // outputs as HwndName { hwnd: registry name, window_name: output name }
+    pub fn monitors(&self) -> Vec<HwndName> {
+        self.state
+            .monitors
+            .iter()
+            .map(|m| HwndName {
+                hwnd: m.global as isize,
+                window_name: m.name.clone(),
+            })
+            .collect()
+    }
+
+    fn monitor(&self, hwnd: isize) -> Result<&Monitor, Error> {
+        self.state
+            .monitors
+            .iter()
+            .find(|m| m.global as isize == hwnd)
+            .ok_or(Error::TargetNotFound(Target::Window(hwnd)))
+    }
+
+    // bounding box of all outputs in compositor space
+    fn layout(&self) -> Region {
+        let m: Vec<Region> = self.state.monitors.iter().map(Monitor::logical).collect();
+        let x = m.iter().map(|r| r.x).min().unwrap_or(0);
+        let y = m.iter().map(|r| r.y).min().unwrap_or(0);
+        let right = m.iter().map(|r| r.x + r.width).max().unwrap_or(0);
+        let bottom = m.iter().map(|r| r.y + r.height).max().unwrap_or(0);
+        Region::new(x, y, right - x, bottom - y)
+    }
+
+    // one screencopy frame of a whole output
+    fn copy(&mut self, output: &WlOutput) -> Result<RgbBuf, Error> {
+        let qh = self.queue.handle();
+        self.state.frame = Frame::default();
+        let frame = self.manager.capture_output(0, output, &qh, ());
+
+        let done = |f: &Frame| f.failed || f.buffer_done || (self.version < 3 && f.offered);
+        let mut dispatched = Ok(0);
+        while dispatched.is_ok() && !done(&self.state.frame) {
+            dispatched = self.queue.blocking_dispatch(&mut self.state);
+        }
+        let (format, width, height, stride) = match (&dispatched, self.state.frame.buffer) {
+            (Ok(_), Some((format, w, h, stride))) if !self.state.frame.failed => {
+                (format, w, h, stride)
+            }
+            _ => {
+                frame.destroy();
+                dispatched?;
+                return Err(Error::ScreencopyFailed);
+            }
+        };
+
+        let size = stride as usize * height as usize;
+        let file = memfd(size)?;
+        let pool = self.shm.create_pool(file.as_fd(), size as i32, &qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            &qh,
+            (),
+        );
+        frame.copy(&buffer);
+        let mut dispatched = Ok(0);
+        while dispatched.is_ok() && !self.state.frame.ready && !self.state.frame.failed {
+            dispatched = self.queue.blocking_dispatch(&mut self.state);
+        }
+        frame.destroy();
+        buffer.destroy();
+        pool.destroy();
+        dispatched?;
+        if self.state.frame.failed {
+            return Err(Error::ScreencopyFailed);
+        }
+
+        let mut data = vec![0; size];
+        file.read_exact_at(&mut data, 0).map_err(Error::WaylandIo)?;
+        let buf = unpack(format, data, width, height, stride)?;
+        Ok(match self.state.frame.y_invert {
+            true => buf.flip_vertical(),
+            false => buf,
+        })
+    }
+}
+
+// anonymous shared file the compositor copies into
+fn memfd(size: usize) -> Result<File, Error> {
+    let file = unsafe {
+        let fd = libc::memfd_create(c"win-screenshot".as_ptr(), libc::MFD_CLOEXEC);
+        if fd == -1 {
+            return Err(Error::WaylandIo(std::io::Error::last_os_error()));
+        }
+        File::from_raw_fd(fd)
+    };
+    file.set_len(size as u64).map_err(Error::WaylandIo)?;
+    Ok(file)
+}
+
+fn supported(format: wl_shm::Format) -> bool {
+    matches!(
+        format,
+        wl_shm::Format::Argb8888
+            | wl_shm::Format::Xrgb8888
+            | wl_shm::Format::Abgr8888
+            | wl_shm::Format::Xbgr8888
+            | wl_shm::Format::Rgb565
+    )
+}
+
+pub(crate) fn unpack(
+    format: wl_shm::Format,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    stride: u32,
+) -> Result<RgbBuf, Error> {
+    // wl_shm formats are little endian, Argb8888 is B, G, R, A in memory
+    let (format, packed) = match format {
+        wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888 => (PixelFormat::Bgra8, None),
+        wl_shm::Format::Abgr8888 | wl_shm::Format::Xbgr8888 => (PixelFormat::Rgba8, None),
+        wl_shm::Format::Rgb565 => (
+            PixelFormat::Bgra8,
+            Some(Packed {
+                bits_per_pixel: 16,
+                red_mask: 0xf800,
+                green_mask: 0x07e0,
+                blue_mask: 0x001f,
+                big_endian: false,
+            }),
+        ),
+        _ => return Err(Error::ScreencopyFailed),
+    };
+    if let Some(packed) = packed {
+        return Ok(packed.unpack(&pixels, width, height, stride as usize)?);
+    }
+    let row = width as usize * 4;
+    let pixels = match stride as usize == row {
+        true => pixels,
+        false => pixels
+            .chunks(stride as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect(),
+    };
+    // X formats leave alpha undefined, the default AlphaMode::Opaque fills it in
+    Ok(RgbBuf::new(pixels, width, height, format)?)
+}
+
+// copy buf onto canvas at x, y, clipped, both Bgra8
+fn blit(canvas: &mut RgbBuf, buf: &RgbBuf, x: i32, y: i32) {
+    let bounds = Region::new(0, 0, canvas.width as i32, canvas.height as i32);
+    let Some(r) = Region::new(x, y, buf.width as i32, buf.height as i32).intersect(&bounds) else {
+        return;
+    };
+    let row = canvas.width as usize * 4;
+    let len = r.width as usize * 4;
+    for dy in 0..r.height {
+        let src = ((r.y - y + dy) as usize * buf.width as usize + (r.x - x) as usize) * 4;
+        let dst = (r.y + dy) as usize * row + r.x as usize * 4;
+        canvas.pixels[dst..dst + len].copy_from_slice(&buf.pixels[src..src + len]);
+    }
+}
+
+impl CaptureBackend for WaylandBackend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        Ok(self.monitors())
+    }
+
+    fn geometry(&mut self, target: Target, _area: Area) -> Result<Geometry, Error> {
+        let (width, height) = match target {
+            Target::Display => {
+                let l = self.layout();
+                (l.width, l.height)
+            }
+            // output pixels turned upright
+            Target::Window(hwnd) => {
+                let m = self.monitor(hwnd)?;
+                logical_size(m.width, m.height, m.transform, 1)
+            }
+        };
+        Ok(Geometry {
+            width,
+            height,
+            mirrored: false,
+        })
+    }
+
+    fn grab(&mut self, target: Target, _area: Area, src: Region) -> Result<RgbBuf, Error> {
+        let full = match target {
+            Target::Window(hwnd) => {
+                let m = self.monitor(hwnd)?.clone();
+                untransform(self.copy(&m.output)?, m.transform)
+            }
+            Target::Display => {
+                let layout = self.layout();
+                let mut canvas = RgbBuf {
+                    pixels: vec![
+                        0;
+                        RgbBuf::len_for(
+                            layout.width as u32,
+                            layout.height as u32,
+                            PixelFormat::Bgra8
+                        )
+                    ],
+                    width: layout.width as u32,
+                    height: layout.height as u32,
+                    format: PixelFormat::Bgra8,
+                };
+                for m in self.state.monitors.clone() {
+                    let buf = self.copy(&m.output)?.into_format(PixelFormat::Bgra8);
+                    // buffers are in output pixels, the layout in compositor space
+                    let mut buf = untransform(buf, m.transform);
+                    let r = m.logical();
+                    if (buf.width, buf.height) != (r.width as u32, r.height as u32) {
+                        buf = buf.resize(r.width as u32, r.height as u32, Filter::Area)?;
+                    }
+                    blit(&mut canvas, &buf, r.x - layout.x, r.y - layout.y);
+                }
+                canvas
+            }
+        };
+        Ok(full.view(src)?.to_owned())
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlOutput, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlOutput,
+        event: wl_output::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(m) = state.monitors.iter_mut().find(|m| m.global == *global) else {
+            return;
+        };
+        match event {
+            wl_output::Event::Geometry {
+                x, y, transform, ..
+            } => {
+                m.x = x;
+                m.y = y;
+                if let WEnum::Value(t) = transform {
+                    m.transform = t;
+                }
+            }
+            wl_output::Event::Scale { factor } => m.scale = factor,
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => {
+                m.width = width;
+                m.height = height;
+            }
+            wl_output::Event::Name { name } => m.name = name,
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let f = &mut state.frame;
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format,
+                width,
+                height,
+                stride,
+            } => {
+                // v3 offers every shm format it can copy to, keep the first usable one
+                f.offered = true;
+                match format {
+                    WEnum::Value(format) if f.buffer.is_none() && supported(format) => {
+                        f.buffer = Some((format, width, height, stride));
+                    }
+                    _ => (),
+                }
+            }
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => f.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert),
+            zwlr_screencopy_frame_v1::Event::BufferDone => f.buffer_done = true,
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => f.ready = true,
+            zwlr_screencopy_frame_v1::Event::Failed => f.failed = true,
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(m) = state.monitors.iter_mut().find(|m| m.global == *global) else {
+            return;
+        };
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                m.logical_position = Some((x, y));
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                m.logical_size = Some((width, height));
+            }
+            _ => (),
+        }
+    }
+}
+
+delegate_noop!(State: ignore WlShm);
+delegate_noop!(State: WlShmPool);
+delegate_noop!(State: ignore WlBuffer);
+delegate_noop!(State: ZwlrScreencopyManagerV1);
+delegate_noop!(State: ZxdgOutputManagerV1);
@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use std::{io, ptr, slice};
+
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectionError, ReplyOrIdError};
+use x11rb::protocol::composite::{ConnectionExt as _, Redirect};
+use x11rb::protocol::damage::{self, ConnectionExt as _, ReportLevel};
+use x11rb::protocol::shm::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    AtomEnum, ConnectionExt, Drawable, ImageFormat, ImageOrder, MapState, Visualid, Window,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::auto::AutoMethods;
+use crate::backend::{CaptureBackend, Geometry};
+use crate::buf::{BufError, RgbBuf};
+use crate::capture::{Area, Using};
+use crate::capturer::WindowCapturer;
+use crate::error::Error;
+use crate::packed::Packed;
+use crate::region::Region;
+use crate::request::Target;
+use crate::utils::HwndName;
+
+// how long a freshly redirected window gets to repaint into its pixmap
+const REPAINT_WAIT: Duration = Duration::from_millis(200);
+// the repaint is taken as done once damage stops arriving for this long
+const REPAINT_QUIET: Duration = Duration::from_millis(10);
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_CLIENT_LIST,
+        _NET_WM_NAME,
+        _NET_WM_STATE,
+        _NET_WM_STATE_HIDDEN,
+        UTF8_STRING,
+    }
+}
+
+// XGetImage over a plain X11 connection, hwnd values are X window ids.
+// Grabs go through a MIT-SHM segment when the server supports it
+pub struct X11Backend {
+    conn: RustConnection,
+    screen: usize,
+    atoms: Atoms,
+    use_shm: bool,
+    shm: Option<Shm>,
+    has_composite: bool,
+    has_damage: bool,
+    composite: bool,
+    // Using::Auto reads the screen first and the composite pixmap when that is blank
+    auto: AutoMethods,
+    // top level windows redirected offscreen by this connection
+    redirected: HashSet<Window>,
+}
+
+// System V shared memory attached on both ends, grown on demand
+struct Shm {
+    seg: shm::Seg,
+    addr: *mut c_void,
+    size: usize,
+}
+
+// the mapping is owned by Shm alone
+unsafe impl Send for Shm {}
+
+impl Shm {
+    fn new(conn: &RustConnection, size: usize) -> Result<Shm, Error> {
+        let (id, addr) = unsafe {
+            let id = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if id == -1 {
+                return Err(Error::Shm(io::Error::last_os_error()));
+            }
+            let addr = libc::shmat(id, ptr::null(), 0);
+            if addr as isize == -1 {
+                let e = io::Error::last_os_error();
+                libc::shmctl(id, libc::IPC_RMID, ptr::null_mut());
+                return Err(Error::Shm(e));
+            }
+            (id, addr)
+        };
+        let seg = conn.generate_id();
+        let attached = seg.map_err(Error::from).and_then(|seg| {
+            conn.shm_attach(seg, id as u32, false)?.check()?;
+            Ok(seg)
+        });
+        unsafe {
+            // freed by the kernel once both sides detach
+            libc::shmctl(id, libc::IPC_RMID, ptr::null_mut());
+        }
+        match attached {
+            Ok(seg) => Ok(Shm { seg, addr, size }),
+            Err(e) => {
+                unsafe { libc::shmdt(addr) };
+                Err(e)
+            }
+        }
+    }
+
+    fn release(self, conn: &RustConnection) {
+        let _ = conn.shm_detach(self.seg);
+        let _ = conn.flush();
+        unsafe { libc::shmdt(self.addr) };
+    }
+}
+
+impl X11Backend {
+    // display from $DISPLAY
+    pub fn connect() -> Result<X11Backend, Error> {
+        X11Backend::connect_to(None)
+    }
+
+    // display name like ":1"
+    pub fn connect_to(display: Option<&str>) -> Result<X11Backend, Error> {
+        let (conn, screen) = x11rb::connect(display)?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let use_shm = match conn.shm_query_version() {
+            Ok(cookie) => cookie.reply().is_ok(),
+            Err(_) => false,
+        };
+        let has_composite = match conn.composite_query_version(0, 4) {
+            Ok(cookie) => cookie.reply().is_ok(),
+            Err(_) => false,
+        };
+        let has_damage = match conn.damage_query_version(1, 1) {
+            Ok(cookie) => cookie.reply().is_ok(),
+            Err(_) => false,
+        };
+        Ok(X11Backend {
+            conn,
+            screen,
+            atoms,
+            use_shm,
+            shm: None,
+            has_composite,
+            has_damage,
+            composite: false,
+            auto: AutoMethods::new(Using::BitBlt, Using::Composite),
+            redirected: HashSet::new(),
+        })
+    }
+
+    // Using::Composite reads windows from their offscreen pixmap, the rest read the screen
+    pub fn set_using(&mut self, using: Using) {
+        self.composite = using == Using::Composite;
+    }
+
+    // false when the server has no MIT-SHM or attaching a segment failed
+    pub fn uses_shm(&self) -> bool {
+        self.use_shm
+    }
+
+    // force the plain XGetImage path, or try MIT-SHM again
+    pub fn set_shm(&mut self, enabled: bool) {
+        self.use_shm = enabled;
+        if !enabled {
+            if let Some(shm) = self.shm.take() {
+                shm.release(&self.conn);
+            }
+        }
+    }
+
+    fn root(&self) -> Window {
+        self.conn.setup().roots[self.screen].root
+    }
+
+    // titled windows that aren't hidden, from the EWMH client list when a window
+    // manager keeps one, otherwise mapped children of the root
+    pub fn window_list(&self) -> Result<Vec<HwndName>, Error> {
+        let clients = self
+            .conn
+            .get_property(
+                false,
+                self.root(),
+                self.atoms._NET_CLIENT_LIST,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        let windows: Vec<Window> = match clients.value32() {
+            Some(list) => list.collect(),
+            None => self.conn.query_tree(self.root())?.reply()?.children,
+        };
+
+        let mut list = Vec::new();
+        for w in windows {
+            match self.visible_name(w) {
+                Ok(Some(name)) => list.push(HwndName {
+                    hwnd: w as isize,
+                    window_name: name,
+                }),
+                Ok(None) => (),
+                // window went away while listing
+                Err(Error::X11(ReplyOrIdError::X11Error(_))) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn find_window(&self, window_name: &str) -> Result<isize, Error> {
+        self.window_list()?
+            .into_iter()
+            .find(|w| w.window_name == window_name)
+            .map(|w| w.hwnd)
+            .ok_or_else(|| Error::FindWindow(io::ErrorKind::NotFound.into()))
+    }
+
+    // None for unmapped, _NET_WM_STATE_HIDDEN (minimized) and untitled windows
+    fn visible_name(&self, w: Window) -> Result<Option<String>, Error> {
+        let attr = self.conn.get_window_attributes(w)?.reply()?;
+        if attr.map_state != MapState::VIEWABLE {
+            return Ok(None);
+        }
+        let state = self
+            .conn
+            .get_property(
+                false,
+                w,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        if let Some(mut atoms) = state.value32() {
+            if atoms.any(|a| a == self.atoms._NET_WM_STATE_HIDDEN) {
+                return Ok(None);
+            }
+        }
+        let name = self.window_name(w)?;
+        Ok(Some(name).filter(|n| !n.is_empty()))
+    }
+
+    // UTF-8 _NET_WM_NAME, falling back to Latin-1 WM_NAME
+    fn window_name(&self, w: Window) -> Result<String, Error> {
+        let prop = self
+            .conn
+            .get_property(
+                false,
+                w,
+                self.atoms._NET_WM_NAME,
+                self.atoms.UTF8_STRING,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        if !prop.value.is_empty() {
+            return Ok(String::from_utf8_lossy(&prop.value).into_owned());
+        }
+        let prop = self
+            .conn
+            .get_property(false, w, AtomEnum::WM_NAME, AtomEnum::ANY, 0, u32::MAX / 4)?
+            .reply()?;
+        Ok(match prop.type_ == u32::from(AtomEnum::STRING) {
+            true => prop.value.iter().map(|&b| b as char).collect(),
+            false => String::from_utf8_lossy(&prop.value).into_owned(),
+        })
+    }
+
+    // ClientOnly is the window itself, Full its top level frame below root
+    fn drawable(&self, target: Target, area: Area) -> Result<Window, Error> {
+        let mut w = match target {
+            Target::Display => return Ok(self.root()),
+            Target::Window(hwnd) => hwnd as Window,
+        };
+        if area == Area::ClientOnly {
+            return Ok(w);
+        }
+        loop {
+            let tree = self.conn.query_tree(w)?.reply()?;
+            if tree.parent == tree.root || tree.parent == x11rb::NONE {
+                return Ok(w);
+            }
+            w = tree.parent;
+        }
+    }
+
+    // visual is needed for pixmaps, windows report their own
+    fn get_image(
+        &mut self,
+        d: Drawable,
+        src: Region,
+        visual: Option<Visualid>,
+    ) -> Result<RgbBuf, Error> {
+        // worst case is 32 bpp, which never needs row padding
+        let size = src.width as usize * src.height as usize * 4;
+        if self.use_shm && self.reserve_shm(size) {
+            if let Some(shm) = &self.shm {
+                return self.shm_grab(shm, d, src, visual);
+            }
+        }
+        let img = self
+            .conn
+            .get_image(
+                ImageFormat::Z_PIXMAP,
+                d,
+                src.x as i16,
+                src.y as i16,
+                src.width as u16,
+                src.height as u16,
+                !0,
+            )?
+            .reply()?;
+        let (packed, pad) = self.packed(img.depth, img.visual, visual)?;
+        let (w, h) = (src.width as u32, src.height as u32);
+        Ok(packed.unpack(&img.data, w, h, packed.stride(w, pad))?)
+    }
+
+    // window contents even where occluded or off screen, via NameWindowPixmap
+    fn composite_grab(&mut self, d: Window, src: Region) -> Result<RgbBuf, Error> {
+        if !self.has_composite {
+            return Err(ConnectionError::UnsupportedExtension.into());
+        }
+        let top = self.drawable(Target::Window(d as isize), Area::Full)?;
+        if !self.redirected.contains(&top) {
+            self.redirect(top)?;
+        }
+        let geo = self.conn.get_geometry(top)?.reply()?;
+        let at = self.conn.translate_coordinates(d, top, 0, 0)?.reply()?;
+        let visual = self.conn.get_window_attributes(top)?.reply()?.visual;
+
+        let pixmap = self.conn.generate_id()?;
+        self.conn.composite_name_window_pixmap(top, pixmap)?;
+        // the pixmap includes the border
+        let bw = geo.border_width as i32;
+        let src = Region::new(
+            src.x + at.dst_x as i32 + bw,
+            src.y + at.dst_y as i32 + bw,
+            src.width,
+            src.height,
+        );
+        let buf = self.get_image(pixmap, src, Some(visual));
+        let _ = self.conn.free_pixmap(pixmap);
+        buf
+    }
+
+    // the new pixmap only holds what was on screen until the expose repaint lands,
+    // damage on the window tells when that happened
+    fn redirect(&mut self, top: Window) -> Result<(), Error> {
+        let damage = match self.has_damage {
+            true => {
+                let id = self.conn.generate_id()?;
+                self.conn
+                    .damage_create(id, top, ReportLevel::RAW_RECTANGLES)?;
+                Some(id)
+            }
+            false => None,
+        };
+        // automatic keeps the server drawing the window on screen as before
+        self.conn
+            .composite_redirect_window(top, Redirect::AUTOMATIC)?
+            .check()?;
+        self.redirected.insert(top);
+        if let Some(id) = damage {
+            let settled = self.wait_repaint(id);
+            self.conn.damage_destroy(id)?;
+            settled?;
+        }
+        Ok(())
+    }
+
+    fn wait_repaint(&self, damage: damage::Damage) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut last = None;
+        loop {
+            match self.conn.poll_for_event()? {
+                Some(Event::DamageNotify(e)) if e.damage == damage => last = Some(Instant::now()),
+                Some(_) => (),
+                None => {
+                    let quiet = last.is_some_and(|t: Instant| t.elapsed() >= REPAINT_QUIET);
+                    if quiet || start.elapsed() >= REPAINT_WAIT {
+                        return Ok(());
+                    }
+                    sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+
+    // false on a remote display or when out of segments, stay on the slow path then
+    fn reserve_shm(&mut self, size: usize) -> bool {
+        if self.shm.as_ref().is_some_and(|shm| shm.size >= size) {
+            return true;
+        }
+        if let Some(old) = self.shm.take() {
+            old.release(&self.conn);
+        }
+        match Shm::new(&self.conn, size) {
+            Ok(shm) => {
+                self.shm = Some(shm);
+                true
+            }
+            Err(_) => {
+                self.use_shm = false;
+                false
+            }
+        }
+    }
+
+    fn shm_grab(
+        &self,
+        shm: &Shm,
+        d: Drawable,
+        src: Region,
+        visual: Option<Visualid>,
+    ) -> Result<RgbBuf, Error> {
+        let img = self
+            .conn
+            .shm_get_image(
+                d,
+                src.x as i16,
+                src.y as i16,
+                src.width as u16,
+                src.height as u16,
+                !0,
+                ImageFormat::Z_PIXMAP.into(),
+                shm.seg,
+                0,
+            )?
+            .reply()?;
+        let data = unsafe { slice::from_raw_parts(shm.addr as *const u8, shm.size) };
+        let data = &data[..(img.size as usize).min(shm.size)];
+        let (packed, pad) = self.packed(img.depth, img.visual, visual)?;
+        let (w, h) = (src.width as u32, src.height as u32);
+        Ok(packed.unpack(data, w, h, packed.stride(w, pad))?)
+    }
+
+    // how pixels of a GetImage reply are laid out
+    fn packed(
+        &self,
+        depth: u8,
+        visual: Visualid,
+        fallback: Option<Visualid>,
+    ) -> Result<(Packed, u32), Error> {
+        let setup = self.conn.setup();
+        let screen = &setup.roots[self.screen];
+        let visual = match visual {
+            x11rb::NONE => fallback.unwrap_or(screen.root_visual),
+            v => v,
+        };
+        let vt = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|d| d.visuals.iter())
+            .find(|v| v.visual_id == visual);
+        let format = setup.pixmap_formats.iter().find(|f| f.depth == depth);
+        match (vt, format) {
+            (Some(vt), Some(f)) => Ok((
+                Packed {
+                    bits_per_pixel: f.bits_per_pixel as u32,
+                    red_mask: vt.red_mask,
+                    green_mask: vt.green_mask,
+                    blue_mask: vt.blue_mask,
+                    big_endian: setup.image_byte_order == ImageOrder::MSB_FIRST,
+                },
+                f.scanline_pad as u32,
+            )),
+            _ => Err(BufError::BitsPerPixel(depth as u32).into()),
+        }
+    }
+}
+
+impl CaptureBackend for X11Backend {
+    fn targets(&mut self) -> Result<Vec<HwndName>, Error> {
+        self.window_list()
+    }
+
+    fn geometry(&mut self, target: Target, area: Area) -> Result<Geometry, Error> {
+        let d = self.drawable(target, area)?;
+        let geo = self.conn.get_geometry(d)?.reply()?;
+        Ok(Geometry {
+            width: geo.width as i32,
+            height: geo.height as i32,
+            mirrored: false,
+        })
+    }
+
+    fn grab(&mut self, target: Target, area: Area, src: Region) -> Result<RgbBuf, Error> {
+        let d = self.drawable(target, area)?;
+        match (self.composite, target) {
+            (true, Target::Window(_)) => self.composite_grab(d, src),
+            _ => self.get_image(d, src, None),
+        }
+    }
+
+    fn set_using(&mut self, using: Using) {
+        X11Backend::set_using(self, using)
+    }
+
+    // no fallback without the composite extension
+    fn auto_methods(&mut self) -> Option<&mut AutoMethods> {
+        self.has_composite.then_some(&mut self.auto)
+    }
+}
+
+impl Drop for X11Backend {
+    fn drop(&mut self) {
+        if let Some(shm) = self.shm.take() {
+            shm.release(&self.conn);
+        }
+    }
+}
+
+// one request captured over and over on a kept connection and SHM segment
+pub type X11Capturer = WindowCapturer<X11Backend>;
@@ -0,0 +1,188 @@
+use crate::buf::{BufError, PixelFormat, RgbBuf};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum YuvLayout {
+    // Y plane, then U and V planes at quarter resolution
+    I420,
+    // Y plane, then one interleaved UV plane at quarter resolution
+    Nv12,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorMatrix {
+    Bt601,
+    Bt709,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorRange {
+    // 0..=255 for luma and chroma
+    Full,
+    // 16..=235 for luma, 16..=240 for chroma
+    Limited,
+}
+
+impl ColorMatrix {
+    fn kr_kb(self) -> (f32, f32) {
+        match self {
+            ColorMatrix::Bt601 => (0.299, 0.114),
+            ColorMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+impl ColorRange {
+    // (luma offset, luma scale, chroma scale)
+    fn scale(self) -> (f32, f32, f32) {
+        match self {
+            ColorRange::Full => (0.0, 255.0, 255.0),
+            ColorRange::Limited => (16.0, 219.0, 224.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct YuvFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub layout: YuvLayout,
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+}
+
+fn clamp_u8(v: f32) -> u8 {
+    (v + 0.5).clamp(0.0, 255.0) as u8
+}
+
+impl YuvFrame {
+    // odd sizes round the chroma planes up
+    pub fn chroma_size(width: u32, height: u32) -> (u32, u32) {
+        (width.div_ceil(2), height.div_ceil(2))
+    }
+
+    pub fn len_for(width: u32, height: u32) -> usize {
+        let (cw, ch) = YuvFrame::chroma_size(width, height);
+        width as usize * height as usize + 2 * cw as usize * ch as usize
+    }
+
+    pub fn planes(&self) -> Vec<&[u8]> {
+        let y = self.width as usize * self.height as usize;
+        let (cw, ch) = YuvFrame::chroma_size(self.width, self.height);
+        let c = cw as usize * ch as usize;
+        match self.layout {
+            YuvLayout::I420 => vec![
+                &self.data[..y],
+                &self.data[y..y + c],
+                &self.data[y + c..y + 2 * c],
+            ],
+            YuvLayout::Nv12 => vec![&self.data[..y], &self.data[y..y + 2 * c]],
+        }
+    }
+
+    pub fn from_rgb(
+        buf: &RgbBuf,
+        layout: YuvLayout,
+        matrix: ColorMatrix,
+        range: ColorRange,
+    ) -> Result<YuvFrame, BufError> {
+        buf.check()?;
+        let (w, h) = (buf.width as usize, buf.height as usize);
+        let (cw, ch) = YuvFrame::chroma_size(buf.width, buf.height);
+        let (cw, ch) = (cw as usize, ch as usize);
+        let (kr, kb) = matrix.kr_kb();
+        let (yoff, yscale, cscale) = range.scale();
+        let bpp = buf.format.bytes_per_pixel();
+
+        let mut data = vec![0; YuvFrame::len_for(buf.width, buf.height)];
+        // summed Pb, Pr and pixel count for each chroma sample
+        let mut chroma = vec![(0f32, 0f32, 0u32); cw * ch];
+        for (y, row) in buf.as_view().rows().enumerate() {
+            for (x, px) in row.chunks_exact(bpp).enumerate() {
+                let [r, g, b, _] = buf.format.read(px);
+                let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+                let l = kr * r + (1.0 - kr - kb) * g + kb * b;
+                data[y * w + x] = clamp_u8(yoff + yscale * l);
+                let c = &mut chroma[y / 2 * cw + x / 2];
+                c.0 += (b - l) / (2.0 * (1.0 - kb));
+                c.1 += (r - l) / (2.0 * (1.0 - kr));
+                c.2 += 1;
+            }
+        }
+
+        let planes = &mut data[w * h..];
+        for (i, &(pb, pr, n)) in chroma.iter().enumerate() {
+            let u = clamp_u8(128.0 + cscale * pb / n as f32);
+            let v = clamp_u8(128.0 + cscale * pr / n as f32);
+            match layout {
+                YuvLayout::I420 => {
+                    planes[i] = u;
+                    planes[cw * ch + i] = v;
+                }
+                YuvLayout::Nv12 => {
+                    planes[2 * i] = u;
+                    planes[2 * i + 1] = v;
+                }
+            }
+        }
+
+        Ok(YuvFrame {
+            data,
+            width: buf.width,
+            height: buf.height,
+            layout,
+            matrix,
+            range,
+        })
+    }
+
+    pub fn to_rgb(&self, format: PixelFormat) -> Result<RgbBuf, BufError> {
+        let expected = YuvFrame::len_for(self.width, self.height);
+        if self.data.len() != expected {
+            return Err(BufError::SizeMismatch {
+                expected,
+                actual: self.data.len(),
+            });
+        }
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (cw, ch) = YuvFrame::chroma_size(self.width, self.height);
+        let (cw, ch) = (cw as usize, ch as usize);
+        let (kr, kb) = self.matrix.kr_kb();
+        let (yoff, yscale, cscale) = self.range.scale();
+        let bpp = format.bytes_per_pixel();
+
+        let mut pixels = vec![0; RgbBuf::len_for(self.width, self.height, format)];
+        for y in 0..h {
+            for x in 0..w {
+                let i = y / 2 * cw + x / 2;
+                let (u, v) = match self.layout {
+                    YuvLayout::I420 => (self.data[w * h + i], self.data[w * h + cw * ch + i]),
+                    YuvLayout::Nv12 => (self.data[w * h + 2 * i], self.data[w * h + 2 * i + 1]),
+                };
+                let l = (self.data[y * w + x] as f32 - yoff) / yscale;
+                let pb = (u as f32 - 128.0) / cscale;
+                let pr = (v as f32 - 128.0) / cscale;
+                let r = l + 2.0 * (1.0 - kr) * pr;
+                let b = l + 2.0 * (1.0 - kb) * pb;
+                let g = (l - kr * r - kb * b) / (1.0 - kr - kb);
+                let p = (y * w + x) * bpp;
+                format.write(
+                    [
+                        clamp_u8(r * 255.0),
+                        clamp_u8(g * 255.0),
+                        clamp_u8(b * 255.0),
+                        255,
+                    ],
+                    &mut pixels[p..p + bpp],
+                );
+            }
+        }
+
+        Ok(RgbBuf {
+            pixels,
+            width: self.width,
+            height: self.height,
+            format,
+        })
+    }
+}